@@ -0,0 +1,157 @@
+#include <stdio.h>
+#include <stdlib.h>
+#include <stdbool.h>
+#include <string.h>
+#include <math.h>
+#include <stdint.h>
+#include <inttypes.h>
+#include <ctype.h>
+#include <errno.h>
+#include <limits.h>
+
+static char *h8_concat(const char *a, const char *b) {
+    size_t a_len = strlen(a), b_len = strlen(b);
+    char *result = malloc(a_len + b_len + 1);
+    if (result == NULL) {
+        fprintf(stderr, "Out of memory\n");
+        exit(1);
+    }
+    memcpy(result, a, a_len);
+    memcpy(result + a_len, b, b_len + 1);
+    return result;
+}
+
+#ifdef NDEBUG
+#define H8_INDEX(index, len, name, line) (index)
+#else
+#define H8_INDEX(index, len, name, line) h8_check_index((index), (len), (name), (line))
+static int64_t h8_check_index(int64_t index, int64_t len, const char *name, int line) {
+    if (index < 0 || index >= len) {
+        fprintf(stderr, "Panic at line %d: index %" PRId64 " is out of bounds for `%s` of length %" PRId64 "\n", line, index, name, len);
+        exit(101);
+    }
+    return index;
+}
+#endif
+
+typedef struct {
+    void *data;
+    int64_t len;
+    int64_t cap;
+    size_t item_size;
+} h8_list;
+
+static h8_list *h8_list_new(size_t item_size) {
+    h8_list *list = calloc(1, sizeof(h8_list));
+    if (list == NULL) {
+        fprintf(stderr, "Out of memory\n");
+        exit(1);
+    }
+    list->item_size = item_size;
+    return list;
+}
+
+static void *h8_list_push(h8_list *list) {
+    if (list->len == list->cap) {
+        list->cap = list->cap == 0 ? 4 : list->cap * 2;
+        list->data = realloc(list->data, list->cap * list->item_size);
+        if (list->data == NULL) {
+            fprintf(stderr, "Out of memory\n");
+            exit(1);
+        }
+    }
+    return (char *)list->data + list->item_size * list->len++;
+}
+
+static void *h8_list_pop(h8_list *list) {
+    if (list->len == 0) {
+        fprintf(stderr, "Panic: cannot pop from an empty list\n");
+        exit(101);
+    }
+    return (char *)list->data + list->item_size * --list->len;
+}
+
+static void *h8_list_at(h8_list *list, int64_t index) {
+    if (index < 0 || index >= list->len) {
+        fprintf(stderr, "Panic: index %" PRId64 " is out of bounds for list of length %" PRId64 "\n", index, list->len);
+        exit(101);
+    }
+    return (char *)list->data + list->item_size * index;
+}
+
+/* Function value: a function taking `env` before its arguments */
+typedef struct {
+    void (*fn)(void);
+    void *env;
+} h8_closure;
+
+static void *h8_env_new(size_t size) {
+    void *env = malloc(size);
+    if (env == NULL) {
+        fprintf(stderr, "Out of memory\n");
+        exit(1);
+    }
+    return env;
+}
+
+static char *h8_int_to_str(int64_t value) {
+    char *result = malloc(24);
+    if (result == NULL) {
+        fprintf(stderr, "Out of memory\n");
+        exit(1);
+    }
+    snprintf(result, 24, "%" PRId64, value);
+    return result;
+}
+
+static char *h8_uint_to_str(uint64_t value) {
+    char *result = malloc(24);
+    if (result == NULL) {
+        fprintf(stderr, "Out of memory\n");
+        exit(1);
+    }
+    snprintf(result, 24, "%" PRIu64, value);
+    return result;
+}
+
+/* Parses a whole decimal `int`, setting `ok` to whether the text is one */
+static int h8_parse_int(const char *text, bool *ok) {
+    char *end;
+    long value;
+    errno = 0;
+    value = strtol(text, &end, 10);
+    *ok = end != text && *end == '\0' && !isspace((unsigned char)text[0]) && errno == 0
+        && value >= INT_MIN && value <= INT_MAX;
+    return *ok ? (int)value : 0;
+}
+void print_hello(char* arg);
+int sum(int a, int b);
+int func();
+
+
+void print_hello(char* arg) {
+printf("Hello, ");
+printf("%s\n", arg);
+}
+int sum(int a, int b) {
+return a + b;
+}
+int func() {
+int temp = sum(2, 2);;
+return temp;
+}
+
+int main(void){
+print_hello("HTML8!");
+int temp = func();;
+printf("%d\n", temp);
+temp++;
+printf("%d\n", temp);
+{
+int name = 0;
+for(; name < temp * 2; name++){
+printf("%d\n", name);
+}
+}
+return 0;
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{
+    build_code_tree, preprocess_code_tree,
+    types::{DefinitionType, NodeType},
+};
+use crate::{
+    errors::{simple::SimpleError, ErrorKind},
+    lift::main_state,
+    parser::{
+        types::{ASTBody, ASTNode, ASTProp, PropType},
+        Parser,
+    },
+};
+
+/// Definitions exported by one imported file
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub path: PathBuf,
+    #[allow(clippy::vec_box)]
+    pub definitions: Vec<Box<NodeType>>,
+    pub direct: bool, // Imported by the file itself, rather than only by its modules
+}
+
+pub struct Importer {
+    chain: Vec<PathBuf>, // Files currently being imported, used to find cycles
+    loaded: HashMap<PathBuf, Vec<Module>>,
+}
+
+impl Importer {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            chain: vec![Self::canonical(path)],
+            loaded: HashMap::new(),
+        }
+    }
+
+    /// Removes `<import>` tags from `<head>` and loads the files they point to.
+    /// Returned modules are ordered so every module comes after its own imports.
+    pub fn resolve(&mut self, tree: &mut ASTNode, dir: &Path) -> Vec<Module> {
+        let mut modules: Vec<Module> = Vec::new();
+
+        let head: Option<&mut ASTNode> = tree.children.iter_mut().find_map(|child| match child {
+            ASTBody::Tag(node) if node.name == "head" => Some(node.as_mut()),
+            _ => None,
+        });
+        let imports: Vec<ASTBody> = match head {
+            Some(head) => {
+                let (imports, children): (Vec<ASTBody>, Vec<ASTBody>) =
+                    head.children.drain(..).partition(
+                        |child| matches!(child, ASTBody::Tag(node) if node.name == "import"),
+                    );
+                head.children = children;
+                imports
+            }
+            None => Vec::new(),
+        };
+        Self::check_misplaced(tree);
+
+        imports.into_iter().for_each(|import: ASTBody| {
+            let ASTBody::Tag(node) = import else {
+                unreachable!()
+            };
+            let loaded: Vec<Module> = self.load(&dir.join(Self::get_src(&node)));
+            let path: PathBuf = loaded.last().unwrap().path.clone();
+            loaded.into_iter().for_each(|mut module: Module| {
+                module.direct = module.path == path;
+                match modules
+                    .iter_mut()
+                    .find(|m: &&mut Module| m.path == module.path)
+                {
+                    Some(m) => m.direct |= module.direct,
+                    None => modules.push(module),
+                }
+            });
+        });
+
+        modules
+    }
+
+    fn load(&mut self, path: &Path) -> Vec<Module> {
+        let path: PathBuf = Self::canonical(path);
+
+        if let Some(start) = self.chain.iter().position(|p: &PathBuf| *p == path) {
+            let cycle: Vec<String> = self.chain[start..]
+                .iter()
+                .chain([&path])
+                .map(|p: &PathBuf| Self::display(p))
+                .collect();
+            SimpleError::error(
+                &format!("Import cycle: {}", cycle.join(" -> ")),
+                ErrorKind::Import,
+            );
+        }
+
+        if let Some(modules) = self.loaded.get(&path) {
+            return modules.clone();
+        }
+
+        let contents: String = fs::read_to_string(&path).unwrap_or_else(|e| {
+            SimpleError::error(
+                &format!("Cannot read `{}`: {}", Self::display(&path), e),
+                ErrorKind::Import,
+            )
+        });
+        let mut tree: ASTNode = Parser::new(contents).parse();
+
+        self.chain.push(path.clone());
+        let mut modules: Vec<Module> = self.resolve(&mut tree, path.parent().unwrap());
+
+        // Exported functions are moved to the importer's `<head>`, away from the module's `<main>`
+        let checked: NodeType = build_code_tree(tree.clone(), &modules);
+        if let Some((function, variable)) = main_state(checked).first() {
+            SimpleError::error(
+                &format!(
+                    "Function `{}` of `{}` cannot be imported, as it uses variable `{}` of its `<main>`. Only `const` variables can be shared",
+                    function,
+                    Self::display(&path),
+                    variable
+                ),
+                ErrorKind::Import,
+            );
+        }
+        // Taken before std and imports are added, and checked again by the importer
+        let definitions: Vec<Box<NodeType>> = Self::exports(preprocess_code_tree(tree));
+        self.chain.pop();
+
+        modules.push(Module {
+            path: path.clone(),
+            definitions,
+            direct: false,
+        });
+        self.loaded.insert(path, modules.clone());
+        modules
+    }
+
+    /// Functions, consts and types declared at the top level of a module
+    #[allow(clippy::vec_box)]
+    fn exports(tree: NodeType) -> Vec<Box<NodeType>> {
+        let NodeType::BLOCK(html) = tree else {
+            unreachable!()
+        };
+        html.children
+            .into_iter()
+            .flat_map(|child: Box<NodeType>| match *child {
+                NodeType::BLOCK(block) => block.children,
+                _ => unreachable!(),
+            })
+            .filter(|child| match child.as_ref() {
+                NodeType::DEFINITION(DefinitionType::Function(fds)) => fds.must_be_compiled,
+                NodeType::DEFINITION(DefinitionType::Variable(vds)) => vds.is_const,
+                NodeType::DEFINITION(DefinitionType::Struct(_) | DefinitionType::Enum(_)) => true,
+                _ => false,
+            })
+            .collect()
+    }
+
+    /// `<import>` tags left after those of `<head>` are taken out are misplaced
+    fn check_misplaced(node: &ASTNode) {
+        (node.name == "import").then(|| {
+            SimpleError::error(
+                &format!(
+                    "`<import>` at line {} is only allowed in `<head>`",
+                    node.line
+                ),
+                ErrorKind::Import,
+            )
+        });
+        node.children.iter().for_each(|child: &ASTBody| {
+            if let ASTBody::Tag(node) = child {
+                Self::check_misplaced(node)
+            }
+        });
+    }
+
+    fn get_src(node: &ASTNode) -> String {
+        match node.props.iter().find(|p: &&ASTProp| p.name == "src") {
+            Some(ASTProp {
+                value: Some(PropType::Literal(src)),
+                ..
+            }) => src.clone(),
+            _ => SimpleError::error(
+                &format!("`<import>` at line {} requires a `src` path", node.line),
+                ErrorKind::Import,
+            ),
+        }
+    }
+
+    fn canonical(path: &Path) -> PathBuf {
+        path.canonicalize().unwrap_or_else(|_| {
+            SimpleError::error(
+                &format!("File `{}` not found", path.display()),
+                ErrorKind::Import,
+            )
+        })
+    }
+
+    /// Path relative to the working directory when possible
+    fn display(path: &Path) -> String {
+        std::env::current_dir()
+            .ok()
+            .and_then(|dir: PathBuf| path.strip_prefix(dir).ok().map(Path::to_path_buf))
+            .unwrap_or(path.to_path_buf())
+            .display()
+            .to_string()
+    }
+}
@@ -2,46 +2,124 @@ use crate::{
     definitions::start_def_check,
     errors::{simple::SimpleError, ErrorKind},
     libs::std::Std,
-    math::{ExprToken, MathParser},
+    math::{ExprToken, MathParser, VariableType},
     parser::types::{ASTBody, ASTNode, PropType},
     types::typechecker::start_types_check,
 };
 use types::{
-    ArgStruct, AssignEnum, AssignStruct, BlockStruct, BlockType, CallArgStruct, CallStruct,
-    DataType, DefinitionType, ForStruct, FunctionDefinitionStruct, NodeType, ServiceBlockType,
-    VariableDefinitionStruct,
+    ArgStruct, ArrayDefinitionStruct, AssignEnum, AssignStruct, BlockStruct, BlockType,
+    BranchStruct, CallArgStruct, CallStruct, CaseStruct, DataType, DefinitionType,
+    EnumDefinitionStruct, ForEachStruct, ForStruct, FunctionDefinitionStruct, IfStruct,
+    MatchStruct, NodeType, ServiceBlockType, StructDefinitionStruct, VariableDefinitionStruct,
+    WhileStruct,
 };
 
+pub mod imports;
 pub mod types;
 use crate::parser::types::ASTProp;
+use imports::{Importer, Module};
 use regex::Regex;
+use std::collections::HashSet;
+use std::path::Path;
 
-fn is_valid_identifier(s: &str) -> bool {
-    Regex::new(r"^[a-zA-Z_]+$").unwrap().is_match(s)
+/// Words that have a meaning inside expressions
+const RESERVED_WORDS: &[&str] = &["true", "false", "as", "if", "then", "else"];
+
+pub fn is_valid_identifier(s: &str) -> bool {
+    Regex::new(r"^[a-zA-Z_]+$").unwrap().is_match(s) && !RESERVED_WORDS.contains(&s)
+}
+
+pub fn start_generating_code_tree(mut tree: ASTNode, path: &Path) -> NodeType {
+    let modules: Vec<Module> =
+        Importer::new(path).resolve(&mut tree, path.parent().unwrap_or(Path::new(".")));
+    build_code_tree(tree, &modules)
 }
 
-pub fn start_generating_code_tree(tree: ASTNode) -> NodeType {
+/// Builds and checks the tree of one file, with imported definitions placed before its code
+fn build_code_tree(tree: ASTNode, modules: &[Module]) -> NodeType {
     let mut tree: NodeType = preprocess_code_tree(tree);
 
-    match tree {
+    let (imported, hidden): (usize, HashSet<String>) = match tree {
         NodeType::BLOCK(ref mut block_struct) if block_struct.tag == BlockType::Html => {
-            for ref mut child in block_struct.children.iter_mut() {
-                match child.as_mut() {
-                    NodeType::BLOCK(ref mut block_struct)
-                        if block_struct.tag == BlockType::Main =>
+            let tags: Vec<BlockType> = block_struct
+                .children
+                .iter()
+                .map(|child| match child.as_ref() {
+                    NodeType::BLOCK(block_struct)
+                        if matches!(block_struct.tag, BlockType::Head | BlockType::Main) =>
                     {
-                        block_struct.children.splice(0..0, Std::use_lib());
+                        block_struct.tag
                     }
-                    NodeType::BLOCK(ref mut block_struct)
-                        if block_struct.tag == BlockType::Head => {} // Will be used later
                     _ => SimpleError::error("Unexpected tag inside `html`!", ErrorKind::Parsing),
-                }
+                })
+                .collect();
+
+            match tags.as_slice() {
+                [BlockType::Head, BlockType::Main] => {}
+                [BlockType::Main] => block_struct.children.insert(
+                    0,
+                    Box::new(NodeType::BLOCK(BlockStruct {
+                        tag: BlockType::Head,
+                        children: Vec::new(),
+                    })),
+                ),
+                _ => SimpleError::error(
+                    "`html` must contain `<main>`, optionally preceded by `<head>`",
+                    ErrorKind::Parsing,
+                ),
             }
+
+            // Std and imported definitions are placed at the start of `<head>`
+            let NodeType::BLOCK(ref mut head) = block_struct.children[0].as_mut() else {
+                unreachable!()
+            };
+            let imported: Vec<Box<NodeType>> = modules
+                .iter()
+                .flat_map(|module: &Module| module.definitions.clone())
+                .collect();
+            let program: Vec<&DefinitionType> = head
+                .children
+                .iter()
+                .chain(imported.iter())
+                .filter_map(|child| match child.as_ref() {
+                    NodeType::DEFINITION(definition) => Some(definition),
+                    _ => None,
+                })
+                .collect();
+
+            // Modules imported only by other modules are not visible to the program
+            let names = |definitions: &[Box<NodeType>]| -> Vec<String> {
+                definitions
+                    .iter()
+                    .filter_map(|child| match child.as_ref() {
+                        NodeType::DEFINITION(definition) => Some(definition.name().to_string()),
+                        _ => None,
+                    })
+                    .collect()
+            };
+            let mut hidden: HashSet<String> = modules
+                .iter()
+                .filter(|module: &&Module| !module.direct)
+                .flat_map(|module: &Module| names(&module.definitions))
+                .collect();
+            modules
+                .iter()
+                .filter(|module: &&Module| module.direct)
+                .flat_map(|module: &Module| names(&module.definitions))
+                .chain(names(&head.children))
+                .for_each(|name: String| {
+                    hidden.remove(&name);
+                });
+
+            let lib: Vec<Box<NodeType>> = Std::use_lib(&program);
+            let len: usize = lib.len() + imported.len();
+            head.children.splice(0..0, lib.into_iter().chain(imported));
+            (len, hidden)
         }
         _ => SimpleError::error("Unexpected root tag!", ErrorKind::Parsing),
-    }
+    };
 
-    start_def_check(&mut tree);
+    start_def_check(&mut tree, imported, &hidden);
     start_types_check(&mut tree);
     tree
 }
@@ -49,22 +127,83 @@ pub fn start_generating_code_tree(tree: ASTNode) -> NodeType {
 #[derive(Debug, Clone)]
 enum TempNodeType {
     Definition(DataType),
+    Array,
+    List,
+    Struct,
+    Enum,
+    Fn,
+    Let,
     Block(BlockType),
     ServiceBlock(BlockType),
     Call,
     Assign,
 }
 
-fn get_data_type(str: String) -> Option<DataType> {
+pub fn get_data_type(str: String) -> Option<DataType> {
     match str {
         s if s == "int" => Some(DataType::Int),
+        s if s == "i8" => Some(DataType::I8),
+        s if s == "i16" => Some(DataType::I16),
+        s if s == "i32" => Some(DataType::I32),
+        s if s == "i64" => Some(DataType::I64),
+        s if s == "u8" => Some(DataType::U8),
+        s if s == "u16" => Some(DataType::U16),
+        s if s == "u32" => Some(DataType::U32),
+        s if s == "u64" => Some(DataType::U64),
         s if s == "str" => Some(DataType::Str),
         s if s == "bool" => Some(DataType::Bool),
+        s if s.trim_start().starts_with("fn(") => {
+            get_fn_type(&s.split_whitespace().collect::<String>())
+        }
+        s if is_valid_identifier(&s) => Some(DataType::Struct(s)), // Resolved in `definitions`
         _ => None,
     }
 }
 
-fn generate_call_args(props: Vec<ASTProp>) -> Vec<CallArgStruct> {
+/// Parses a function type like `fn(int,str)->bool`, the return type can be left out for `void`
+fn get_fn_type(str: &str) -> Option<DataType> {
+    let rest: &str = str.strip_prefix("fn(")?;
+
+    // Argument types are split at commas outside of nested function types
+    let mut args: Vec<&str> = Vec::new();
+    let mut depth: usize = 0;
+    let mut start: usize = 0;
+    let mut end: Option<usize> = None;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            ')' => {
+                end = Some(i);
+                break;
+            }
+            ',' if depth == 0 => {
+                args.push(&rest[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    let end: usize = end?;
+    if !args.is_empty() || start < end {
+        args.push(&rest[start..end]);
+    }
+
+    let args: Vec<DataType> = args
+        .into_iter()
+        .map(|arg: &str| get_data_type(arg.to_string()))
+        .collect::<Option<Vec<DataType>>>()?;
+    let data_type: DataType = match rest[end + 1..].strip_prefix("->") {
+        Some("void") => DataType::Void,
+        Some(return_type) => get_data_type(return_type.to_string())?,
+        None if rest.len() == end + 1 => DataType::Void,
+        None => return None,
+    };
+
+    Some(DataType::Fn(args, Box::new(data_type)))
+}
+
+fn generate_call_args(props: Vec<ASTProp>, line: usize) -> Vec<CallArgStruct> {
     props
         .iter()
         .map(|prop: &ASTProp| {
@@ -73,7 +212,7 @@ fn generate_call_args(props: Vec<ASTProp>) -> Vec<CallArgStruct> {
                 .value
                 .map(|val: PropType| match val {
                     PropType::Literal(s) => Some(ExprToken::Literal(s.to_string())),
-                    PropType::Var(s) => Some(MathParser::new(s.chars()).parse_expr()),
+                    PropType::Var(s) => Some(MathParser::new(s.chars(), line).parse_expr()),
                 })
                 .unwrap_or(None);
 
@@ -85,13 +224,469 @@ fn generate_call_args(props: Vec<ASTProp>) -> Vec<CallArgStruct> {
         .collect()
 }
 
+/// Reads defaults of function arguments, e.g. `sep="str" sep-default={" "}`
+fn set_arg_defaults(args: &mut [ArgStruct], tree: &ASTNode) {
+    tree.props
+        .iter()
+        .filter_map(|prop: &ASTProp| Some((prop.name.strip_suffix("-default")?, prop)))
+        .for_each(|(name, prop)| {
+            let arg: &mut ArgStruct = args
+                .iter_mut()
+                .find(|arg: &&mut ArgStruct| arg.name == name)
+                .unwrap_or_else(|| {
+                    SimpleError::error(
+                        &format!("Default value for unknown argument `{}`", name),
+                        ErrorKind::Parsing,
+                    )
+                });
+            arg.default = generate_call_args(vec![prop.clone()], tree.line)
+                .remove(0)
+                .value;
+            arg.default.is_none().then(|| {
+                SimpleError::error(
+                    &format!("Default value of argument `{}` cannot be a flag", name),
+                    ErrorKind::Parsing,
+                )
+            });
+        });
+}
+
+/// Reads type parameters of a function, e.g. `generics="T U"`
+fn get_generics(tree: &ASTNode) -> Vec<String> {
+    let Some(prop) = tree.props.iter().find(|p: &&ASTProp| p.name == "generics") else {
+        return Vec::new();
+    };
+    let names: Vec<String> = match &prop.value {
+        Some(PropType::Literal(names)) => names.split_whitespace().map(String::from).collect(),
+        _ => Vec::new(),
+    };
+    names.is_empty().then(|| {
+        SimpleError::error(
+            "`generics` must list the names of type parameters",
+            ErrorKind::Parsing,
+        )
+    });
+
+    names.iter().enumerate().for_each(|(i, name)| {
+        if get_data_type(name.clone()) != Some(DataType::Struct(name.clone())) {
+            SimpleError::error(
+                &format!("Invalid type parameter `{}`", name),
+                ErrorKind::Parsing,
+            );
+        }
+        names[..i].contains(name).then(|| {
+            SimpleError::error(
+                &format!("Type parameter `{}` is listed more than once", name),
+                ErrorKind::Parsing,
+            )
+        });
+    });
+    names
+}
+
+fn get_condition(props: Vec<ASTProp>, line: usize) -> ExprToken {
+    generate_call_args(props, line)
+        .into_iter()
+        .find(|a: &CallArgStruct| a.name.eq("cond"))
+        .unwrap_or_else(|| {
+            SimpleError::error(
+                "Argument `cond` in conditional block is required",
+                ErrorKind::Parsing,
+            )
+        })
+        .value
+        .unwrap_or_else(|| {
+            SimpleError::error("Argument `cond` cannot be a flag", ErrorKind::Parsing)
+        })
+}
+
+fn get_definition_name(props: &[ASTProp]) -> String {
+    if let Some(prop) = props.iter().find(|p: &&ASTProp| p.name == "name") {
+        if let Some(PropType::Literal(new_name)) = &prop.value {
+            if !is_valid_identifier(new_name) {
+                SimpleError::error(
+                    &format!("`{}` is not valid name!", new_name),
+                    ErrorKind::Parsing,
+                )
+            }
+            new_name.to_string()
+        } else {
+            SimpleError::error(
+                "Cannot use dynamic value for defining a variable name!",
+                ErrorKind::Parsing,
+            )
+        }
+    } else {
+        SimpleError::error("You should define name for variable!", ErrorKind::Parsing)
+    }
+}
+
+/// Reads the element type of a collection from the `of` attribute
+fn get_element_type(tree: &ASTNode, name: &str, allowed_props: &[&str]) -> DataType {
+    (!tree.children.is_empty()).then(|| {
+        SimpleError::error(
+            &format!("`{}` definition cannot have a body", name),
+            ErrorKind::Parsing,
+        )
+    });
+
+    tree.props
+        .iter()
+        .any(|p: &ASTProp| !allowed_props.contains(&p.name.as_str()))
+        .then(|| {
+            SimpleError::error(
+                &format!("Unexpected argument for `{}`", name),
+                ErrorKind::Parsing,
+            )
+        });
+
+    match tree.props.iter().find(|p: &&ASTProp| p.name == "of") {
+        Some(ASTProp {
+            value: Some(PropType::Literal(type_name)),
+            ..
+        }) => get_data_type(type_name.clone()).unwrap_or_else(|| {
+            SimpleError::error(
+                &format!("Unknown element type `{}` for `{}`", type_name, name),
+                ErrorKind::Parsing,
+            )
+        }),
+        _ => SimpleError::error(
+            &format!("Argument `of` for `{}` is required", name),
+            ErrorKind::Parsing,
+        ),
+    }
+}
+
+/// Builds `<array name="xs" of="int" len={10} />`
+fn get_array_definition(tree: &ASTNode) -> ArrayDefinitionStruct {
+    let name: String = get_definition_name(&tree.props);
+    let element_type: DataType = get_element_type(tree, &name, &["name", "of", "len"]);
+
+    let len: ExprToken = generate_call_args(tree.props.clone(), tree.line)
+        .into_iter()
+        .find(|a: &CallArgStruct| a.name.eq("len"))
+        .and_then(|a: CallArgStruct| a.value)
+        .unwrap_or_else(|| {
+            SimpleError::error(
+                &format!("Argument `len` for `{}` is required", name),
+                ErrorKind::Parsing,
+            )
+        });
+
+    ArrayDefinitionStruct {
+        name,
+        element_type,
+        len,
+    }
+}
+
+/// Builds `<list name="ys" of="int" />`, an empty list
+fn get_list_definition(tree: &ASTNode) -> VariableDefinitionStruct {
+    let name: String = get_definition_name(&tree.props);
+    let element_type: DataType = get_element_type(tree, &name, &["name", "of"]);
+
+    VariableDefinitionStruct {
+        data_type: DataType::List(Box::new(element_type)),
+        name,
+        value: AssignEnum::None,
+        is_const: false,
+    }
+}
+
+/// Builds `<fn name="op" type="fn(int,int)->int">add</fn>`, a variable holding a function
+fn get_fn_definition(tree: &ASTNode) -> VariableDefinitionStruct {
+    let name: String = get_definition_name(&tree.props);
+
+    tree.props
+        .iter()
+        .any(|p: &ASTProp| !["name", "type", "const"].contains(&p.name.as_str()))
+        .then(|| {
+            SimpleError::error(
+                &format!("Unexpected argument for `{}`", name),
+                ErrorKind::Parsing,
+            )
+        });
+
+    let data_type: DataType = match tree.props.iter().find(|p: &&ASTProp| p.name == "type") {
+        Some(ASTProp {
+            value: Some(PropType::Literal(type_name)),
+            ..
+        }) => match get_data_type(type_name.clone()) {
+            Some(data_type @ DataType::Fn(..)) => data_type,
+            _ => SimpleError::error(
+                &format!("`{}` is not a function type", type_name),
+                ErrorKind::Parsing,
+            ),
+        },
+        _ => SimpleError::error(
+            &format!("Argument `type` for `{}` is required", name),
+            ErrorKind::Parsing,
+        ),
+    };
+
+    let value: AssignEnum = match tree.children.as_slice() {
+        [ASTBody::String(str)] => {
+            AssignEnum::Expr(MathParser::new(str.chars(), tree.line).parse_expr())
+        }
+        [ASTBody::Tag(tag)] => AssignEnum::Call(Box::new(preprocess_code_tree(*tag.clone()))),
+        _ => SimpleError::error(
+            &format!("`{}` must be set to a function", name),
+            ErrorKind::Parsing,
+        ),
+    };
+
+    VariableDefinitionStruct {
+        data_type,
+        name,
+        value,
+        is_const: tree
+            .props
+            .iter()
+            .any(|p: &ASTProp| p.name == "const" && p.value.is_none()),
+    }
+}
+
+/// Builds `<let name="x">expr</let>`, a variable whose type is inferred by the type check
+fn get_let_definition(tree: &ASTNode) -> VariableDefinitionStruct {
+    let name: String = get_definition_name(&tree.props);
+
+    tree.props
+        .iter()
+        .any(|p: &ASTProp| !["name", "const"].contains(&p.name.as_str()))
+        .then(|| {
+            SimpleError::error(
+                &format!("Variable `{}` definition cannot take arguments", name),
+                ErrorKind::Parsing,
+            )
+        });
+
+    let value: AssignEnum = match tree.children.as_slice() {
+        [ASTBody::String(str)] => {
+            AssignEnum::Expr(MathParser::new(str.chars(), tree.line).parse_expr())
+        }
+        [ASTBody::Tag(tag)] => AssignEnum::Call(Box::new(preprocess_code_tree(*tag.clone()))),
+        _ => SimpleError::error(
+            &format!("Variable `{}` needs a value to infer its type from", name),
+            ErrorKind::Parsing,
+        ),
+    };
+
+    VariableDefinitionStruct {
+        data_type: DataType::Any,
+        name,
+        value,
+        is_const: tree
+            .props
+            .iter()
+            .any(|p: &ASTProp| p.name == "const" && p.value.is_none()),
+    }
+}
+
+/// Builds `<struct name="Point" x="int" y="int" />`
+fn get_struct_definition(tree: &ASTNode) -> StructDefinitionStruct {
+    let name: String = get_definition_name(&tree.props);
+
+    (!tree.children.is_empty()).then(|| {
+        SimpleError::error(
+            &format!("Struct `{}` definition cannot have a body", name),
+            ErrorKind::Parsing,
+        )
+    });
+
+    let fields: Vec<ArgStruct> = tree
+        .props
+        .iter()
+        .filter(|prop: &&ASTProp| prop.name != "name")
+        .map(|prop: &ASTProp| {
+            let data_type: Option<DataType> = match &prop.value {
+                Some(PropType::Literal(type_name)) => get_data_type(type_name.clone()),
+                _ => None,
+            };
+            ArgStruct::new(
+                prop.name.clone(),
+                data_type.unwrap_or_else(|| {
+                    SimpleError::error(
+                        &format!("Unknown type for field `{}` of `{}`", prop.name, name),
+                        ErrorKind::Parsing,
+                    )
+                }),
+            )
+        })
+        .collect();
+
+    fields.is_empty().then(|| {
+        SimpleError::error(
+            &format!("Struct `{}` must have at least one field", name),
+            ErrorKind::Parsing,
+        )
+    });
+
+    StructDefinitionStruct { name, fields }
+}
+
+/// Builds `<enum name="Color" values="red green blue" />`
+fn get_enum_definition(tree: &ASTNode) -> EnumDefinitionStruct {
+    let name: String = get_definition_name(&tree.props);
+
+    (!tree.children.is_empty()).then(|| {
+        SimpleError::error(
+            &format!("Enum `{}` definition cannot have a body", name),
+            ErrorKind::Parsing,
+        )
+    });
+
+    let values: Vec<String> = match tree.props.iter().find(|p: &&ASTProp| p.name == "values") {
+        Some(ASTProp {
+            value: Some(PropType::Literal(values)),
+            ..
+        }) => values.split_whitespace().map(String::from).collect(),
+        _ => SimpleError::error(
+            &format!("Argument `values` for enum `{}` is required", name),
+            ErrorKind::Parsing,
+        ),
+    };
+
+    if let Some(value) = values.iter().find(|value| !is_valid_identifier(value)) {
+        SimpleError::error(
+            &format!("`{}` is not a valid value for enum `{}`", value, name),
+            ErrorKind::Parsing,
+        )
+    }
+
+    values.is_empty().then(|| {
+        SimpleError::error(
+            &format!("Enum `{}` must have at least one value", name),
+            ErrorKind::Parsing,
+        )
+    });
+
+    EnumDefinitionStruct { name, values }
+}
+
+/// Adds a `<case>` or `<default>` block to `match_struct`
+fn push_case(match_struct: &mut MatchStruct, node: ASTNode) {
+    let children: Vec<Box<NodeType>> = preprocess_children(node.children);
+
+    match node.name.as_str() {
+        "case" => match node.props.iter().find(|p: &&ASTProp| p.name == "is") {
+            Some(ASTProp {
+                value: Some(PropType::Literal(values)),
+                ..
+            }) => match_struct.cases.push(CaseStruct {
+                values: values.split_whitespace().map(String::from).collect(),
+                children,
+            }),
+            _ => SimpleError::error(
+                "Argument `is` in case block must be a literal",
+                ErrorKind::Parsing,
+            ),
+        },
+        "default" if match_struct.default.is_none() => match_struct.default = Some(children),
+        "default" => SimpleError::error(
+            "Match block can have only one `<default>`",
+            ErrorKind::Parsing,
+        ),
+        _ => SimpleError::error(
+            "Only `<case>` and `<default>` are allowed inside `<match>`",
+            ErrorKind::Parsing,
+        ),
+    }
+}
+
+/// Builds `<for each="x" in={xs}>`
+fn get_for_each(props: Vec<ASTProp>, line: usize) -> ForEachStruct {
+    let args: Vec<CallArgStruct> = generate_call_args(props, line);
+
+    let item_name: String = match args.iter().find(|a| a.name.eq("each")) {
+        Some(CallArgStruct {
+            value: Some(ExprToken::Literal(name)),
+            ..
+        }) => name.clone(),
+        _ => SimpleError::error("Argument `each` must be a literal", ErrorKind::Parsing),
+    };
+
+    let iterable: ExprToken = args
+        .into_iter()
+        .find(|a| a.name.eq("in"))
+        .and_then(|a: CallArgStruct| a.value)
+        .unwrap_or_else(|| {
+            SimpleError::error(
+                "Argument `in` in for each block is required",
+                ErrorKind::Parsing,
+            )
+        });
+
+    ForEachStruct {
+        item_name,
+        iterable,
+        children: Vec::new(),
+    }
+}
+
+#[allow(clippy::vec_box)]
+fn preprocess_children(nodes: Vec<ASTBody>) -> Vec<Box<NodeType>> {
+    let mut children: Vec<Box<NodeType>> = Vec::new();
+    nodes.into_iter().for_each(|child: ASTBody| match child {
+        ASTBody::Tag(tag) => push_child(&mut children, *tag),
+        ASTBody::String(_) => SimpleError::error(
+            "String tags not supported inside blocks",
+            ErrorKind::Parsing,
+        ),
+    });
+    children
+}
+
+/// Preprocesses `node` and appends it to `children`.
+/// `<elif>` and `<else>` are attached to the preceding `<if>` instead
+#[allow(clippy::vec_box)]
+fn push_child(children: &mut Vec<Box<NodeType>>, node: ASTNode) {
+    if node.name != "elif" && node.name != "else" {
+        children.push(Box::new(preprocess_code_tree(node)));
+        return;
+    }
+
+    let branch_children: Vec<Box<NodeType>> = preprocess_children(node.children);
+
+    match children.last_mut().map(|child| child.as_mut()) {
+        Some(NodeType::ServiceBlock(ServiceBlockType::If(if_struct)))
+            if if_struct.else_children.is_none() =>
+        {
+            match node.name.as_str() {
+                "elif" => if_struct.branches.push(BranchStruct {
+                    condition: get_condition(node.props, node.line),
+                    children: branch_children,
+                }),
+                _ => if_struct.else_children = Some(branch_children),
+            }
+        }
+        _ => SimpleError::error(
+            &format!("`<{}>` must follow `<if>` or `<elif>`", node.name),
+            ErrorKind::Parsing,
+        ),
+    }
+}
+
 fn preprocess_code_tree(tree: ASTNode) -> NodeType {
     let temp_node_type: TempNodeType = match &tree.name {
         // Definitions
         s if s == "int" => TempNodeType::Definition(DataType::Int),
+        s if s == "i8" => TempNodeType::Definition(DataType::I8),
+        s if s == "i16" => TempNodeType::Definition(DataType::I16),
+        s if s == "i32" => TempNodeType::Definition(DataType::I32),
+        s if s == "i64" => TempNodeType::Definition(DataType::I64),
+        s if s == "u8" => TempNodeType::Definition(DataType::U8),
+        s if s == "u16" => TempNodeType::Definition(DataType::U16),
+        s if s == "u32" => TempNodeType::Definition(DataType::U32),
+        s if s == "u64" => TempNodeType::Definition(DataType::U64),
         s if s == "bool" => TempNodeType::Definition(DataType::Bool),
         s if s == "str" => TempNodeType::Definition(DataType::Str),
         s if s == "void" => TempNodeType::Definition(DataType::Void),
+        s if s == "array" => TempNodeType::Array,
+        s if s == "list" => TempNodeType::List,
+        s if s == "struct" => TempNodeType::Struct,
+        s if s == "enum" => TempNodeType::Enum,
+        s if s == "fn" => TempNodeType::Fn,
+        s if s == "let" => TempNodeType::Let,
 
         // Blocks
         s if s == "html" => TempNodeType::Block(BlockType::Html),
@@ -99,16 +694,36 @@ fn preprocess_code_tree(tree: ASTNode) -> NodeType {
         s if s == "main" => TempNodeType::Block(BlockType::Main),
         s if s == "div" => TempNodeType::Block(BlockType::Div),
         s if s == "for" => TempNodeType::ServiceBlock(BlockType::For),
+        s if s == "if" => TempNodeType::ServiceBlock(BlockType::If),
+        s if s == "while" => TempNodeType::ServiceBlock(BlockType::While),
+        s if s == "match" => TempNodeType::ServiceBlock(BlockType::Match),
+        s if s == "elif" || s == "else" => SimpleError::error(
+            &format!("`<{}>` must follow `<if>` or `<elif>`", s),
+            ErrorKind::Parsing,
+        ),
 
         // Assign/Call
         _ if tree.self_closing => TempNodeType::Call,
+
+        // Variables of user types, e.g. `<Color name="c">Color.red</Color>`
+        s if tree.props.iter().any(|p: &ASTProp| p.name == "name") => {
+            TempNodeType::Definition(get_data_type(s.clone()).unwrap_or_else(|| {
+                SimpleError::error(&format!("Unknown type `{}`", s), ErrorKind::Parsing)
+            }))
+        }
         _ => TempNodeType::Assign,
     };
 
     let mut node_type: NodeType = match temp_node_type.clone() {
         TempNodeType::ServiceBlock(block_type) => match block_type {
+            BlockType::For if tree.props.iter().any(|p: &ASTProp| p.name == "each") => {
+                NodeType::ServiceBlock(ServiceBlockType::ForEach(get_for_each(
+                    tree.props.clone(),
+                    tree.line,
+                )))
+            }
             BlockType::For => NodeType::ServiceBlock(ServiceBlockType::For({
-                let args = generate_call_args(tree.props);
+                let args = generate_call_args(tree.props.clone(), tree.line);
 
                 let start = args
                     .iter()
@@ -160,6 +775,16 @@ fn preprocess_code_tree(tree: ASTNode) -> NodeType {
                     _ => SimpleError::error("Argument `i` must be a literal", ErrorKind::Parsing),
                 };
 
+                let step = match args.iter().find(|a| a.name.eq("step")) {
+                    Some(arg) => arg.value.clone().unwrap_or_else(|| {
+                        SimpleError::error("Argument `step` cannot be bool", ErrorKind::Parsing)
+                    }),
+                    None => ExprToken::Number(1),
+                };
+                let inclusive = args
+                    .iter()
+                    .any(|a| a.name.eq("inclusive") && a.value.is_none());
+
                 let children = vec![Box::new(NodeType::DEFINITION(DefinitionType::Variable(
                     VariableDefinitionStruct {
                         data_type: DataType::Int,
@@ -171,43 +796,70 @@ fn preprocess_code_tree(tree: ASTNode) -> NodeType {
                 ForStruct {
                     start,
                     end,
+                    step,
+                    inclusive,
                     iter_name,
                     children,
                 }
             })),
+            BlockType::If => NodeType::ServiceBlock(ServiceBlockType::If(IfStruct {
+                branches: vec![BranchStruct {
+                    condition: get_condition(tree.props, tree.line),
+                    children: Vec::new(),
+                }],
+                else_children: None,
+            })),
+            BlockType::While => NodeType::ServiceBlock(ServiceBlockType::While(WhileStruct {
+                condition: get_condition(tree.props, tree.line),
+                children: Vec::new(),
+            })),
+            BlockType::Match => NodeType::ServiceBlock(ServiceBlockType::Match(MatchStruct {
+                value: generate_call_args(tree.props.clone(), tree.line)
+                    .into_iter()
+                    .find(|a: &CallArgStruct| a.name.eq("on"))
+                    .and_then(|a: CallArgStruct| a.value)
+                    .unwrap_or_else(|| {
+                        SimpleError::error(
+                            "Argument `on` in match block is required",
+                            ErrorKind::Parsing,
+                        )
+                    }),
+                cases: Vec::new(),
+                default: None,
+            })),
             _ => unreachable!(),
         },
         TempNodeType::Block(block_type) => NodeType::BLOCK(BlockStruct {
             tag: block_type,
             children: Vec::new(),
         }),
+        TempNodeType::Array => {
+            NodeType::DEFINITION(DefinitionType::Array(get_array_definition(&tree)))
+        }
+        TempNodeType::List => {
+            NodeType::DEFINITION(DefinitionType::Variable(get_list_definition(&tree)))
+        }
+        TempNodeType::Struct => {
+            NodeType::DEFINITION(DefinitionType::Struct(get_struct_definition(&tree)))
+        }
+        TempNodeType::Enum => {
+            NodeType::DEFINITION(DefinitionType::Enum(get_enum_definition(&tree)))
+        }
+        TempNodeType::Fn => {
+            NodeType::DEFINITION(DefinitionType::Variable(get_fn_definition(&tree)))
+        }
+        TempNodeType::Let => {
+            NodeType::DEFINITION(DefinitionType::Variable(get_let_definition(&tree)))
+        }
         TempNodeType::Definition(data_type) => {
-            let definition_name: String =
-                if let Some(prop) = tree.props.iter().find(|p: &&ASTProp| p.name == "name") {
-                    if let Some(PropType::Literal(new_name)) = &prop.value {
-                        is_valid_identifier(new_name)
-                            .then(|| new_name.to_string())
-                            .unwrap_or_else(|| {
-                                SimpleError::error(
-                                    &format!("`{}` is not valid name!", new_name),
-                                    ErrorKind::Parsing,
-                                )
-                            })
-                    } else {
-                        SimpleError::error(
-                            "Cannot use dynamic value for defining a variable name!",
-                            ErrorKind::Parsing,
-                        )
-                    }
-                } else {
-                    SimpleError::error("You should define name for variable!", ErrorKind::Parsing)
-                };
+            let definition_name: String = get_definition_name(&tree.props);
 
+            // A `const` flag marks a variable, even when its value is a call
             let is_func: bool = (tree.children.len() > 1
                 || !tree
                     .props
                     .iter()
-                    .filter(|p| p.name.ne("name"))
+                    .filter(|p| p.name.ne("name") && !(p.name == "const" && p.value.is_none()))
                     .collect::<Vec<&ASTProp>>()
                     .is_empty())
                 && tree
@@ -217,10 +869,13 @@ fn preprocess_code_tree(tree: ASTNode) -> NodeType {
 
             match is_func {
                 true => {
-                    let args = tree
+                    let mut args: Vec<ArgStruct> = tree
                         .props
                         .iter()
-                        .filter(|prop: &&ASTProp| prop.name != "name")
+                        .filter(|prop: &&ASTProp| {
+                            !["name", "generics"].contains(&prop.name.as_str())
+                                && !prop.name.ends_with("-default")
+                        })
                         .map(|prop: &ASTProp| {
                             let data_type: PropType = prop.value.clone().unwrap_or_else(|| {
                                 SimpleError::error(
@@ -232,9 +887,9 @@ fn preprocess_code_tree(tree: ASTNode) -> NodeType {
                             matches!(data_type, PropType::Literal(_))
                                 .then(|| {
                                     if let PropType::Literal(v) = data_type {
-                                        ArgStruct {
-                                            name: prop.name.clone(),
-                                            data_type: get_data_type(v).unwrap_or_else(|| {
+                                        ArgStruct::new(
+                                            prop.name.clone(),
+                                            get_data_type(v).unwrap_or_else(|| {
                                                 SimpleError::error(
                                                     &format!(
                                                     "Unknown data type for function argument: {:?}",
@@ -243,7 +898,7 @@ fn preprocess_code_tree(tree: ASTNode) -> NodeType {
                                                     ErrorKind::Parsing,
                                                 )
                                             }),
-                                        }
+                                        )
                                     } else {
                                         unreachable!()
                                     }
@@ -257,21 +912,25 @@ fn preprocess_code_tree(tree: ASTNode) -> NodeType {
                                         ErrorKind::Parsing,
                                     )
                                 })
-                        });
+                        })
+                        .collect();
+                    set_arg_defaults(&mut args, &tree);
 
                     NodeType::DEFINITION(DefinitionType::Function(FunctionDefinitionStruct {
                         data_type,
                         name: definition_name,
                         children: Vec::new(),
-                        args: args.collect(),
+                        args,
+                        captures: Vec::new(),
                         must_be_compiled: true,
+                        generics: get_generics(&tree),
                     }))
                 }
                 false => match tree.children.len() {
                     1 => {
                         let value = match &tree.children[0] {
                             ASTBody::String(str) => AssignEnum::Expr(
-                                MathParser::new(str.to_string().chars()).parse_expr(),
+                                MathParser::new(str.to_string().chars(), tree.line).parse_expr(),
                             ),
                             ASTBody::Tag(tag) => {
                                 AssignEnum::Call(Box::new(preprocess_code_tree(*tag.clone())))
@@ -283,14 +942,10 @@ fn preprocess_code_tree(tree: ASTNode) -> NodeType {
                             .iter()
                             .any(|prop: &ASTProp| prop.name == "const" && prop.value.is_none());
 
-                        (tree
-                            .props
+                        tree.props
                             .iter()
-                            .filter(|prop: &&ASTProp| prop.name != "const" && prop.name != "name")
-                            .count()
-                            == 0)
-                            .then_some(())
-                            .unwrap_or_else(|| {
+                            .any(|prop: &ASTProp| prop.name != "const" && prop.name != "name")
+                            .then(|| {
                                 SimpleError::error(
                                     &format!(
                                         "Variable `{}` definition cannot take arguments",
@@ -316,33 +971,73 @@ fn preprocess_code_tree(tree: ASTNode) -> NodeType {
         }
         TempNodeType::Call => NodeType::CALL(CallStruct {
             calling_name: tree.name,
-            args: generate_call_args(tree.props),
+            args: generate_call_args(tree.props, tree.line),
+            callee: None,
+            overload: 0,
+            type_args: Vec::new(),
+            builtin: false,
+            line: tree.line,
         }),
-        TempNodeType::Assign => NodeType::ASSIGN(AssignStruct::new(tree.name)),
+        TempNodeType::Assign if tree.name.contains('.') => {
+            // Field assignment, e.g. `<p.x>1</p.x>`
+            let element: ExprToken = MathParser::new(tree.name.chars(), tree.line).parse_expr();
+            let name: String = tree.name.split('.').next().unwrap().to_string();
+            NodeType::ASSIGN(AssignStruct::new(name, Some(element)))
+        }
+        TempNodeType::Assign => {
+            let element: Option<ExprToken> = generate_call_args(tree.props, tree.line)
+                .into_iter()
+                .find(|a: &CallArgStruct| a.name.eq("at"))
+                .map(|a: CallArgStruct| {
+                    let index: ExprToken = a.value.unwrap_or_else(|| {
+                        SimpleError::error("Argument `at` cannot be a flag", ErrorKind::Parsing)
+                    });
+                    ExprToken::Index(
+                        Box::new(ExprToken::Variable(VariableType::new(
+                            tree.name.clone(),
+                            DataType::Any,
+                            false,
+                        ))),
+                        Box::new(index),
+                        tree.line,
+                    )
+                });
+            NodeType::ASSIGN(AssignStruct::new(tree.name, element))
+        }
     };
 
+    let line: usize = tree.line;
     tree.children
         .into_iter()
         .for_each(|child: ASTBody| match child {
             ASTBody::Tag(node) => match node_type {
-                NodeType::BLOCK(ref mut block_struct) => block_struct
-                    .children
-                    .push(Box::new(preprocess_code_tree(*node))),
+                NodeType::BLOCK(ref mut block_struct) => {
+                    push_child(&mut block_struct.children, *node)
+                }
                 NodeType::DEFINITION(ref mut definition_type) => match definition_type {
-                    DefinitionType::Function(fds) => {
-                        fds.children.push(Box::new(preprocess_code_tree(*node)))
-                    }
-                    DefinitionType::Variable(_) => {}
+                    DefinitionType::Function(fds) => push_child(&mut fds.children, *node),
+                    DefinitionType::Variable(_)
+                    | DefinitionType::Array(_)
+                    | DefinitionType::Struct(_)
+                    | DefinitionType::Enum(_) => {}
                 },
                 NodeType::ASSIGN(ref mut assign_struct) => {
                     assign_struct.body = AssignEnum::Call(Box::new(preprocess_code_tree(*node)))
                 }
                 NodeType::ServiceBlock(ref mut service_block_type) => match service_block_type {
                     ServiceBlockType::For(for_struct) => {
-                        for_struct
-                            .children
-                            .push(Box::new(preprocess_code_tree(*node)));
+                        push_child(&mut for_struct.children, *node)
+                    }
+                    ServiceBlockType::ForEach(for_each_struct) => {
+                        push_child(&mut for_each_struct.children, *node)
+                    }
+                    ServiceBlockType::If(if_struct) => {
+                        push_child(&mut if_struct.branches[0].children, *node)
+                    }
+                    ServiceBlockType::While(while_struct) => {
+                        push_child(&mut while_struct.children, *node)
                     }
+                    ServiceBlockType::Match(match_struct) => push_case(match_struct, *node),
                 },
                 _ => unreachable!(),
             },
@@ -356,10 +1051,14 @@ fn preprocess_code_tree(tree: ASTNode) -> NodeType {
                         "Cannot use string tags inside function",
                         ErrorKind::Parsing,
                     ),
-                    DefinitionType::Variable(_) => {}
+                    DefinitionType::Variable(_)
+                    | DefinitionType::Array(_)
+                    | DefinitionType::Struct(_)
+                    | DefinitionType::Enum(_) => {}
                 },
                 NodeType::ASSIGN(ref mut assign_struct) => {
-                    assign_struct.body = AssignEnum::Expr(MathParser::new(s.chars()).parse_expr())
+                    assign_struct.body =
+                        AssignEnum::Expr(MathParser::new(s.chars(), line).parse_expr())
                 }
                 _ => unreachable!(),
             },
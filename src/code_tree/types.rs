@@ -1,14 +1,123 @@
+use std::collections::HashMap;
+
 use crate::math::ExprToken;
 
 // Possible data types
 #[derive(Debug, Clone, PartialEq)]
 pub enum DataType {
     Int,
+    I8,
+    I16,
+    I32,
+    I64,
+    U8,
+    U16,
+    U32,
+    U64,
     Bool,
     Str,
     Void,
+    Array(Box<DataType>, usize), // Element type and length
+    List(Box<DataType>),
+    Struct(String),                   // User-defined struct, by name
+    Enum(String),                     // User-defined enum, by name
+    Fn(Vec<DataType>, Box<DataType>), // Function value, by argument and return types
+
+    Any,             // Internal type, cannot be accessed from code
+    Generic(String), // Type parameter of generic functions
+}
+
+impl DataType {
+    /// Inclusive range of values representable by an integer type
+    pub fn int_bounds(&self) -> Option<(i128, i128)> {
+        match self {
+            DataType::Int | DataType::I32 => Some((i32::MIN as i128, i32::MAX as i128)),
+            DataType::I8 => Some((i8::MIN as i128, i8::MAX as i128)),
+            DataType::I16 => Some((i16::MIN as i128, i16::MAX as i128)),
+            DataType::I64 => Some((i64::MIN as i128, i64::MAX as i128)),
+            DataType::U8 => Some((0, u8::MAX as i128)),
+            DataType::U16 => Some((0, u16::MAX as i128)),
+            DataType::U32 => Some((0, u32::MAX as i128)),
+            DataType::U64 => Some((0, u64::MAX as i128)),
+            _ => None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.int_bounds().is_some()
+    }
 
-    Any, // Internal type, cannot be accessed from code
+    pub fn is_generic(&self) -> bool {
+        match self {
+            DataType::Generic(_) => true,
+            DataType::Array(element_type, _) | DataType::List(element_type) => {
+                element_type.is_generic()
+            }
+            DataType::Fn(args, data_type) => {
+                args.iter().any(DataType::is_generic) || data_type.is_generic()
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether all type parameters of this type have a binding
+    pub fn is_bound(&self, bindings: &HashMap<String, DataType>) -> bool {
+        match self {
+            DataType::Generic(name) => bindings.contains_key(name),
+            DataType::Array(element_type, _) | DataType::List(element_type) => {
+                element_type.is_bound(bindings)
+            }
+            DataType::Fn(args, data_type) => {
+                args.iter().all(|arg| arg.is_bound(bindings)) && data_type.is_bound(bindings)
+            }
+            _ => true,
+        }
+    }
+
+    /// Matches `actual` against this type, binding its type parameters
+    pub fn bind(&self, actual: &DataType, bindings: &mut HashMap<String, DataType>) -> bool {
+        match (self, actual) {
+            (DataType::Any, _) => true,
+            (DataType::Generic(name), _) => match bindings.get(name) {
+                Some(bound) => bound == actual,
+                None => {
+                    bindings.insert(name.clone(), actual.clone());
+                    true
+                }
+            },
+            (DataType::List(expected), DataType::List(actual)) => expected.bind(actual, bindings),
+            (DataType::Array(expected, expected_len), DataType::Array(actual, actual_len)) => {
+                expected_len == actual_len && expected.bind(actual, bindings)
+            }
+            (DataType::Fn(expected_args, expected), DataType::Fn(actual_args, actual)) => {
+                expected_args.len() == actual_args.len()
+                    && expected_args
+                        .iter()
+                        .zip(actual_args)
+                        .all(|(expected, actual)| expected.bind(actual, bindings))
+                    && expected.bind(actual, bindings)
+            }
+            _ => self == actual,
+        }
+    }
+
+    /// Replaces bound type parameters
+    pub fn substitute(&self, bindings: &HashMap<String, DataType>) -> DataType {
+        match self {
+            DataType::Generic(name) => bindings.get(name).cloned().unwrap_or(self.clone()),
+            DataType::List(element_type) => {
+                DataType::List(Box::new(element_type.substitute(bindings)))
+            }
+            DataType::Array(element_type, len) => {
+                DataType::Array(Box::new(element_type.substitute(bindings)), *len)
+            }
+            DataType::Fn(args, data_type) => DataType::Fn(
+                args.iter().map(|arg| arg.substitute(bindings)).collect(),
+                Box::new(data_type.substitute(bindings)),
+            ),
+            _ => self.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -36,6 +145,9 @@ pub enum BlockType {
     Main,
     Div,
     For,
+    If,
+    While,
+    Match,
 }
 
 // ----------- Definition Type -------------
@@ -44,17 +156,41 @@ pub enum BlockType {
 pub enum DefinitionType {
     Function(FunctionDefinitionStruct),
     Variable(VariableDefinitionStruct),
+    Array(ArrayDefinitionStruct),
+    Struct(StructDefinitionStruct),
+    Enum(EnumDefinitionStruct),
+}
+
+impl DefinitionType {
+    pub fn name(&self) -> &str {
+        match self {
+            DefinitionType::Function(fds) => &fds.name,
+            DefinitionType::Variable(vds) => &vds.name,
+            DefinitionType::Array(ads) => &ads.name,
+            DefinitionType::Struct(sds) => &sds.name,
+            DefinitionType::Enum(eds) => &eds.name,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ArgStruct {
     pub name: String,
     pub data_type: DataType,
+    pub default: Option<ExprToken>, // Value used when the argument is omitted, e.g. `sep-default={" "}`
+    pub optional: bool, // Can be omitted without a default, like the value of `<return />`
+    pub writes: bool,   // Written by the callee, like `ok` of `<parse_int>`
 }
 
 impl ArgStruct {
     pub fn new(name: String, data_type: DataType) -> ArgStruct {
-        Self { name, data_type }
+        Self {
+            name,
+            data_type,
+            default: None,
+            optional: false,
+            writes: false,
+        }
     }
 }
 
@@ -66,6 +202,25 @@ pub struct VariableDefinitionStruct {
     pub is_const: bool,
 }
 
+#[derive(Debug, Clone)]
+pub struct ArrayDefinitionStruct {
+    pub name: String,
+    pub element_type: DataType,
+    pub len: ExprToken,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructDefinitionStruct {
+    pub name: String,
+    pub fields: Vec<ArgStruct>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumDefinitionStruct {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub struct FunctionDefinitionStruct {
@@ -74,7 +229,17 @@ pub struct FunctionDefinitionStruct {
     #[allow(clippy::vec_box)]
     pub children: Vec<Box<NodeType>>,
     pub args: Vec<ArgStruct>,
+    pub captures: Vec<CaptureStruct>, // Filled when the function is moved to file scope
     pub must_be_compiled: bool,
+    pub generics: Vec<String>, // Type parameters, e.g. `generics="T"`
+}
+
+/// Variable of an enclosing block used by a nested function, passed by pointer
+#[derive(Debug, Clone)]
+pub struct CaptureStruct {
+    pub name: String,
+    pub data_type: DataType,
+    pub is_const: bool,
 }
 
 impl FunctionDefinitionStruct {
@@ -89,7 +254,9 @@ impl FunctionDefinitionStruct {
             name,
             children: Vec::new(),
             args,
+            captures: Vec::new(),
             must_be_compiled,
+            generics: Vec::new(),
         }
     }
 }
@@ -106,6 +273,11 @@ pub struct CallArgStruct {
 pub struct CallStruct {
     pub calling_name: String,
     pub args: Vec<CallArgStruct>,
+    pub callee: Option<DataType>, // Type of the called function value, set by the type check
+    pub overload: usize,          // Index among functions sharing the name, set by the type check
+    pub type_args: Vec<DataType>, // Types bound to the type parameters of a generic function
+    pub builtin: bool,            // Calls a std function, set by the type check
+    pub line: usize,
 }
 
 // ----------- Assign Type ---------------
@@ -120,13 +292,15 @@ pub enum AssignEnum {
 #[derive(Debug, Clone)]
 pub struct AssignStruct {
     pub name: String,
+    pub element: Option<ExprToken>, // Assigned part of the variable, e.g. `xs[i]` or `p.x`
     pub body: AssignEnum,
 }
 
 impl AssignStruct {
-    pub fn new(name: String) -> Self {
+    pub fn new(name: String, element: Option<ExprToken>) -> Self {
         AssignStruct {
             name,
+            element,
             body: AssignEnum::None,
         }
     }
@@ -137,12 +311,63 @@ impl AssignStruct {
 #[derive(Debug, Clone)]
 pub enum ServiceBlockType {
     For(ForStruct),
+    ForEach(ForEachStruct),
+    If(IfStruct),
+    While(WhileStruct),
+    Match(MatchStruct),
 }
 
 #[derive(Debug, Clone)]
 pub struct ForStruct {
     pub start: ExprToken,
     pub end: ExprToken,
+    pub step: ExprToken,
+    pub inclusive: bool,
     pub iter_name: String,
+    #[allow(clippy::vec_box)]
+    pub children: Vec<Box<NodeType>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForEachStruct {
+    pub item_name: String,
+    pub iterable: ExprToken,
+    #[allow(clippy::vec_box)]
+    pub children: Vec<Box<NodeType>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BranchStruct {
+    pub condition: ExprToken,
+    #[allow(clippy::vec_box)]
+    pub children: Vec<Box<NodeType>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IfStruct {
+    pub branches: Vec<BranchStruct>,
+    #[allow(clippy::vec_box)]
+    pub else_children: Option<Vec<Box<NodeType>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseStruct {
+    pub values: Vec<String>,
+    #[allow(clippy::vec_box)]
+    pub children: Vec<Box<NodeType>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchStruct {
+    pub value: ExprToken,
+    pub cases: Vec<CaseStruct>,
+    #[allow(clippy::vec_box)]
+    pub default: Option<Vec<Box<NodeType>>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WhileStruct {
+    pub condition: ExprToken,
+    #[allow(clippy::vec_box)]
     pub children: Vec<Box<NodeType>>,
 }
@@ -1,8 +1,9 @@
-use rand::distributions::Alphanumeric;
-use rand::{thread_rng, Rng};
+use std::collections::{HashMap, HashSet};
 
 use crate::code_tree::types::{
-    ArgStruct, AssignEnum, AssignStruct, BlockType, ForStruct, ServiceBlockType,
+    ArgStruct, ArrayDefinitionStruct, AssignEnum, AssignStruct, BlockStruct, BlockType,
+    BranchStruct, CaptureStruct, CaseStruct, EnumDefinitionStruct, ForEachStruct, ForStruct,
+    IfStruct, MatchStruct, ServiceBlockType, StructDefinitionStruct, WhileStruct,
 };
 use crate::{
     code_tree::types::{
@@ -10,42 +11,171 @@ use crate::{
         VariableDefinitionStruct,
     },
     libs::std::Std,
+    lift::{instantiate, lift},
     math::ExprToken,
 };
 
-const C_KEYWORDS: &[&str] = &[
-    "return", "int", "char", "void", "if", "else", "while", "for", "do", "break", "continue",
-    "println", "print",
-];
-
 pub struct CLang {
     pub tree: NodeType,
+    captures: HashMap<String, Vec<String>>, // Variables passed by pointer to each function
+    pointers: Vec<String>,                  // Captured variables of the function being compiled
+    closures: HashSet<String>,              // Functions used as values
+    generics: HashMap<String, FunctionDefinitionStruct>, // Generic functions by name
+    instances: Vec<(String, Vec<DataType>)>, // Generic functions and type arguments of their instances
+    in_match: bool,                          // Inside `<match>` in the innermost loop
+    loop_exit: Option<String>, // Label after the innermost loop, once a `<break />` needs it
+    labels: usize,
 }
 
 impl CompilerCodegen for CLang {
     fn new(tree: NodeType) -> Self {
-        Self { tree }
+        Self {
+            tree,
+            captures: HashMap::new(),
+            pointers: Vec::new(),
+            closures: HashSet::new(),
+            generics: HashMap::new(),
+            instances: Vec::new(),
+            in_match: false,
+            loop_exit: None,
+            labels: 0,
+        }
     }
     fn compile(&mut self) -> String {
-        let statements: String = self._compile(self.tree.clone());
+        let mut tree: NodeType = self.tree.clone();
+        self.closures = lift(&mut tree);
+
+        let NodeType::BLOCK(html) = tree else {
+            unreachable!()
+        };
+        let mut blocks = html.children.into_iter();
+        let (mut globals, init) = match blocks.next().map(|block: Box<NodeType>| *block) {
+            Some(NodeType::BLOCK(head)) => self.compile_head(head),
+            _ => unreachable!(),
+        };
+        let statements: String = blocks
+            .map(|block: Box<NodeType>| self._compile(*block))
+            .collect();
+
+        // Instances are declared after the type definitions, like other functions
+        let (declarations, definitions) = self.compile_instances();
+        globals.insert(1, declarations);
+        globals.push(definitions);
+
         format!(
-            "#include <stdio.h>\n#include <math.h>\nint main(void){{\n{}return 0;\n}}",
+            "{}\n{}\nint main(void){{\n{}{}return 0;\n}}",
+            Std::runtime(),
+            globals.join("\n"),
+            init,
             statements
         )
     }
 }
 
 impl CLang {
-    fn convert_types(data_type: DataType) -> String {
+    pub fn convert_types(data_type: DataType) -> String {
         match data_type {
             DataType::Int => String::from("int"),
-            DataType::Bool => String::from("int"),
-            DataType::Str => String::from("char"),
+            DataType::I8 => String::from("int8_t"),
+            DataType::I16 => String::from("int16_t"),
+            DataType::I32 => String::from("int32_t"),
+            DataType::I64 => String::from("int64_t"),
+            DataType::U8 => String::from("uint8_t"),
+            DataType::U16 => String::from("uint16_t"),
+            DataType::U32 => String::from("uint32_t"),
+            DataType::U64 => String::from("uint64_t"),
+            DataType::Bool => String::from("bool"),
+            DataType::Str => String::from("char*"),
             DataType::Void => String::from("void"),
+            DataType::List(_) => String::from("h8_list*"),
+            DataType::Struct(name) => Self::struct_type(&name),
+            DataType::Enum(name) => Self::enum_type(&name),
+            DataType::Fn(..) => String::from("h8_closure"),
             _ => String::from("int"),
         }
     }
 
+    /// Definitions from `<head>` are placed at file scope.
+    /// Constants that were not folded are assigned at the start of `main`
+    fn compile_head(&mut self, mut head: BlockStruct) -> (Vec<String>, String) {
+        let mut globals: Vec<String> = vec![Self::compile_declarations(&mut head.children)];
+        head.children.iter().for_each(|child| {
+            if let NodeType::DEFINITION(DefinitionType::Function(fds)) = child.as_ref() {
+                let names: Vec<String> = fds.captures.iter().map(|c| c.name.clone()).collect();
+                self.captures.insert(fds.name.clone(), names);
+                if fds.must_be_compiled && self.closures.contains(&fds.name) {
+                    globals.push(Self::compile_closure(fds));
+                }
+            }
+        });
+        let mut init: String = String::new();
+
+        head.children
+            .into_iter()
+            .for_each(|child: Box<NodeType>| match *child {
+                NodeType::DEFINITION(DefinitionType::Function(fds)) if !fds.must_be_compiled => {}
+                NodeType::DEFINITION(DefinitionType::Function(fds)) if !fds.generics.is_empty() => {
+                    self.generics.insert(fds.name.clone(), fds);
+                }
+                NodeType::DEFINITION(DefinitionType::Variable(vds))
+                    if !matches!(
+                        vds.value,
+                        AssignEnum::Expr(
+                            ExprToken::Number(_)
+                                | ExprToken::Bool(_)
+                                | ExprToken::Literal(_)
+                                | ExprToken::Variant(..)
+                        )
+                    ) =>
+                {
+                    globals.push(format!(
+                        "{} {};",
+                        Self::convert_types(vds.data_type.clone()),
+                        vds.name
+                    ));
+                    let value: String = self.compile_value(vds.value, &vds.data_type);
+                    init.push_str(&format!("{} = {};\n", vds.name, value));
+                }
+                node => globals.push(self._compile(node)),
+            });
+
+        (globals, init)
+    }
+
+    /// C name of the instance of a generic function for `type_args`
+    fn instance(&mut self, name: &str, type_args: Vec<DataType>) -> String {
+        let key: (String, Vec<DataType>) = (name.to_string(), type_args);
+        let index: usize = match self.instances.iter().position(|instance| *instance == key) {
+            Some(index) => index,
+            None => {
+                self.instances.push(key);
+                self.instances.len() - 1
+            }
+        };
+        format!("{}_t{}", name, index + 1)
+    }
+
+    /// Compiles the instances of generic functions used by the program.
+    /// Instances can use further instances, which are compiled in turn
+    fn compile_instances(&mut self) -> (String, String) {
+        let mut declarations: Vec<String> = Vec::new();
+        let mut definitions: Vec<String> = Vec::new();
+
+        let mut index: usize = 0;
+        while let Some((name, type_args)) = self.instances.get(index).cloned() {
+            index += 1;
+            let instance: FunctionDefinitionStruct = instantiate(
+                &self.generics[&name],
+                &type_args,
+                format!("{}_t{}", name, index),
+            );
+            declarations.push(format!("{};", Self::fn_signature(&instance)));
+            definitions.push(self.compile_fn(instance));
+        }
+
+        (declarations.join("\n"), definitions.join("\n"))
+    }
+
     fn _compile(&mut self, node: NodeType) -> String {
         match node {
             NodeType::BLOCK(block_struct) => {
@@ -84,6 +214,9 @@ impl CLang {
             NodeType::DEFINITION(definition_type) => match definition_type {
                 DefinitionType::Function(fds) => self.compile_fn(fds),
                 DefinitionType::Variable(vds) => self.compile_var(vds),
+                DefinitionType::Array(ads) => self.compile_array(ads),
+                DefinitionType::Struct(sds) => Self::compile_struct(sds),
+                DefinitionType::Enum(eds) => Self::compile_enum(eds),
             },
             NodeType::CALL(call_struct) if call_struct.calling_name.eq("println") => {
                 Std::compile_println(call_struct)
@@ -100,10 +233,26 @@ impl CLang {
             NodeType::CALL(call_struct) if call_struct.calling_name.eq("dec") => {
                 Std::compile_dec(call_struct)
             }
+            NodeType::CALL(call_struct) if call_struct.calling_name.eq("break") => {
+                self.compile_break()
+            }
+            NodeType::CALL(call_struct) if call_struct.calling_name.eq("continue") => {
+                Std::compile_continue()
+            }
             NodeType::CALL(call_struct) => self.compile_call(call_struct),
             NodeType::ASSIGN(assign_struct) => self.compile_assign(assign_struct),
             NodeType::ServiceBlock(sbt) => match sbt {
-                ServiceBlockType::For(for_struct) => self.compile_for(for_struct),
+                ServiceBlockType::For(for_struct) => {
+                    self.compile_loop(|c: &mut Self| c.compile_for(for_struct))
+                }
+                ServiceBlockType::ForEach(for_each_struct) => {
+                    self.compile_loop(|c: &mut Self| c.compile_for_each(for_each_struct))
+                }
+                ServiceBlockType::If(if_struct) => self.compile_if(if_struct),
+                ServiceBlockType::While(while_struct) => {
+                    self.compile_loop(|c: &mut Self| c.compile_while(while_struct))
+                }
+                ServiceBlockType::Match(match_struct) => self.compile_match(match_struct),
             },
         }
     }
@@ -117,71 +266,302 @@ impl CLang {
                 let stmt_string = self._compile(*child);
                 (!stmt_string.is_empty()).then(|| children.push(stmt_string));
             });
-        let random_name = Self::random_string(7);
+
+        let iter: &str = &for_struct.iter_name;
+        let end: String = Self::process_expr_token(for_struct.end);
+        let (up, down) = match for_struct.inclusive {
+            true => ("<=", ">="),
+            false => ("<", ">"),
+        };
+
+        // Direction is picked at compile time when the step is known,
+        // otherwise the step is evaluated once before the loop
+        let (step_def, condition, increment) = match for_struct.step {
+            ExprToken::Number(1) => (
+                String::new(),
+                format!("{} {} {}", iter, up, end),
+                format!("{}++", iter),
+            ),
+            ExprToken::Number(-1) => (
+                String::new(),
+                format!("{} {} {}", iter, down, end),
+                format!("{}--", iter),
+            ),
+            ExprToken::Number(n) if n > 0 => (
+                String::new(),
+                format!("{} {} {}", iter, up, end),
+                format!("{} += {}", iter, n),
+            ),
+            ExprToken::Number(n) => (
+                String::new(),
+                format!("{} {} {}", iter, down, end),
+                format!("{} -= {}", iter, -n),
+            ),
+            step => {
+                let step_name: String = format!("h8_step_{}", iter);
+                (
+                    format!(
+                        "const int {} = {};\n",
+                        step_name,
+                        Self::process_expr_token(step)
+                    ),
+                    format!(
+                        "({} > 0 ? {} {} {} : {} {} {})",
+                        step_name, iter, up, end, iter, down, end
+                    ),
+                    format!("{} += {}", iter, step_name),
+                )
+            }
+        };
 
         format!(
-            "{}\nloop_{}:\nif({}>={}){{goto end_{};}}\n{}\n{}++;\ngoto loop_{};\nend_{}:",
+            "{{\n{}\n{}for(; {}; {}){{\n{}\n}}\n}}",
             children[0],
-            random_name,
-            for_struct.iter_name,
-            Self::process_expr_token(for_struct.end),
-            random_name,
+            step_def,
+            condition,
+            increment,
             children[1..].join("\n"),
-            for_struct.iter_name,
-            random_name,
-            random_name
         )
     }
 
-    fn compile_var(&mut self, v: VariableDefinitionStruct) -> String {
-        let arr: String = (v.data_type == DataType::Str)
-            .then(|| String::from("*"))
-            .unwrap_or_else(String::new);
+    fn compile_for_each(&mut self, for_each_struct: ForEachStruct) -> String {
+        let (element_type, len) = match for_each_struct.iterable.resolved_type() {
+            DataType::Array(element_type, len) => (*element_type, len),
+            _ => unreachable!(),
+        };
+        let index: String = format!("h8_index_{}", for_each_struct.item_name);
+
+        format!(
+            "for(int {} = 0; {} < {}; {}++){{\n{} {} = {}[{}];\n{}\n}}",
+            index,
+            index,
+            len,
+            index,
+            Self::convert_types(element_type),
+            for_each_struct.item_name,
+            Self::process_expr_token(for_each_struct.iterable),
+            index,
+            self.compile_children(for_each_struct.children),
+        )
+    }
+
+    fn compile_while(&mut self, while_struct: WhileStruct) -> String {
+        format!(
+            "while({}){{\n{}\n}}",
+            Self::process_expr_token(while_struct.condition),
+            self.compile_children(while_struct.children)
+        )
+    }
+
+    /// Compiles a loop, followed by its exit label if a `<break />` inside `<match>` jumps there
+    fn compile_loop(&mut self, compile: impl FnOnce(&mut Self) -> String) -> String {
+        let in_match: bool = std::mem::replace(&mut self.in_match, false);
+        let loop_exit: Option<String> = self.loop_exit.take();
 
-        let value = match v.value {
+        let mut code: String = compile(self);
+        if let Some(label) = std::mem::replace(&mut self.loop_exit, loop_exit) {
+            code = format!("{}\n{}:;", code, label);
+        }
+        self.in_match = in_match;
+        code
+    }
+
+    /// C `break` inside `switch` only leaves the `switch`, so `<match>` jumps past the loop instead
+    fn compile_break(&mut self) -> String {
+        if !self.in_match {
+            return Std::compile_break();
+        }
+        if self.loop_exit.is_none() {
+            self.labels += 1;
+            self.loop_exit = Some(format!("h8_loop_end_{}", self.labels));
+        }
+        format!("goto {};", self.loop_exit.as_ref().unwrap())
+    }
+
+    fn compile_match(&mut self, match_struct: MatchStruct) -> String {
+        let name: String = match match_struct.value.resolved_type() {
+            DataType::Enum(name) => name,
+            _ => unreachable!(),
+        };
+
+        let in_match: bool = std::mem::replace(&mut self.in_match, true);
+        let mut cases: Vec<String> = match_struct
+            .cases
+            .into_iter()
+            .map(|case: CaseStruct| {
+                let labels: Vec<String> = case
+                    .values
+                    .iter()
+                    .map(|value| format!("case {}:", Self::enum_value(&name, value)))
+                    .collect();
+                format!(
+                    "{} {{
+{}
+}} break;",
+                    labels.join(" "),
+                    self.compile_children(case.children)
+                )
+            })
+            .collect();
+
+        if let Some(children) = match_struct.default {
+            cases.push(format!(
+                "default: {{
+{}
+}} break;",
+                self.compile_children(children)
+            ));
+        }
+        self.in_match = in_match;
+
+        format!(
+            "switch({}){{
+{}
+}}",
+            Self::process_expr_token(match_struct.value),
+            cases.join("\n")
+        )
+    }
+
+    fn compile_if(&mut self, if_struct: IfStruct) -> String {
+        let mut branches: Vec<String> = if_struct
+            .branches
+            .into_iter()
+            .map(|branch: BranchStruct| {
+                format!(
+                    "if({}){{\n{}\n}}",
+                    Self::process_expr_token(branch.condition),
+                    self.compile_children(branch.children)
+                )
+            })
+            .collect();
+
+        if let Some(children) = if_struct.else_children {
+            branches.push(format!("{{\n{}\n}}", self.compile_children(children)));
+        }
+
+        branches.join(" else ")
+    }
+
+    #[allow(clippy::vec_box)]
+    fn compile_children(&mut self, children: Vec<Box<NodeType>>) -> String {
+        let mut statements: Vec<String> = Vec::new();
+        children.into_iter().for_each(|child: Box<NodeType>| {
+            let stmt_string = self._compile(*child);
+            (!stmt_string.is_empty()).then(|| statements.push(stmt_string));
+        });
+        statements.join("\n")
+    }
+
+    fn compile_value(&mut self, value: AssignEnum, data_type: &DataType) -> String {
+        match value {
             AssignEnum::Expr(expr_token) => Self::process_expr_token(expr_token),
             AssignEnum::Call(node_type) => match *node_type {
                 NodeType::CALL(call_struct) => self.compile_call(call_struct),
                 _ => unreachable!(),
             },
-            AssignEnum::None => unreachable!(),
-        };
+            AssignEnum::None => match data_type.clone() {
+                DataType::List(element_type) => Std::compile_list_new(*element_type),
+                _ => unreachable!(),
+            },
+        }
+    }
+
+    fn compile_var(&mut self, v: VariableDefinitionStruct) -> String {
+        let value: String = self.compile_value(v.value, &v.data_type);
 
         let is_const = if v.is_const { "const " } else { "" };
 
         format!(
-            "{}{} {}{} = {};",
+            "{}{} {} = {};",
             is_const,
             Self::convert_types(v.data_type),
-            arr,
             v.name,
             value
         )
     }
 
-    fn compile_fn(&mut self, f: FunctionDefinitionStruct) -> String {
-        #[allow(clippy::obfuscated_if_else)]
-        let fn_name: String = is_c_keyword(&f.name)
-            .then(|| format!("{}_func", f.name))
-            .unwrap_or(f.name.clone());
+    fn compile_array(&mut self, a: ArrayDefinitionStruct) -> String {
+        let len: String = Self::process_expr_token(a.len);
+        let definition: String = format!(
+            "{} {}[{}] = {{0}};",
+            Self::convert_types(a.element_type.clone()),
+            a.name,
+            len
+        );
 
-        let mut args: Vec<String> = f
-            .args
-            .iter()
-            .map(|arg: &ArgStruct| {
-                let pointer: String = (arg.data_type == DataType::Str)
-                    .then(|| String::from("*"))
-                    .unwrap_or_else(String::new);
+        // Strings start empty instead of NULL
+        match a.element_type {
+            DataType::Str => format!(
+                "{}\nfor(int h8_index = 0; h8_index < {}; h8_index++) {}[h8_index] = \"\";",
+                definition, len, a.name
+            ),
+            _ => definition,
+        }
+    }
 
-                format!(
-                    "{}{} {}",
-                    Self::convert_types(arg.data_type.clone()),
-                    pointer,
-                    arg.name
-                )
+    fn compile_struct(s: StructDefinitionStruct) -> String {
+        let fields: Vec<String> = s
+            .fields
+            .into_iter()
+            .map(|field: ArgStruct| {
+                format!("{} {};", Self::convert_types(field.data_type), field.name)
             })
-            .collect::<Vec<String>>();
-        args.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()));
+            .collect();
+
+        format!(
+            "typedef struct {{\n{}\n}} {};",
+            fields.join("\n"),
+            Self::struct_type(&s.name)
+        )
+    }
+
+    /// C name of a struct type, prefixed so it cannot clash with types of the C headers
+    fn struct_type(name: &str) -> String {
+        format!("h8_struct_{}", name)
+    }
+
+    fn compile_enum(e: EnumDefinitionStruct) -> String {
+        let values: Vec<String> = e
+            .values
+            .iter()
+            .map(|value| Self::enum_value(&e.name, value))
+            .collect();
+        let names: Vec<String> = e
+            .values
+            .iter()
+            .map(|value| format!("\"{}\"", value))
+            .collect();
+
+        format!(
+            "typedef enum {{ {} }} {};\nconst char *{}[] = {{ {} }};",
+            values.join(", "),
+            Self::enum_type(&e.name),
+            Self::enum_names(&e.name),
+            names.join(", ")
+        )
+    }
+
+    /// C name of an enum type, prefixed so it cannot clash with types of the C headers
+    fn enum_type(name: &str) -> String {
+        format!("h8_enum_{}", name)
+    }
+
+    /// C name of an enum value, prefixed so it cannot clash with user names
+    pub fn enum_value(name: &str, value: &str) -> String {
+        format!("h8_{}_{}", name, value)
+    }
+
+    /// C array holding the names of enum values, used for printing.
+    /// The prefix differs from values, which may be called `names`
+    pub fn enum_names(name: &str) -> String {
+        format!("h8_names_{}", name)
+    }
+
+    fn compile_fn(&mut self, f: FunctionDefinitionStruct) -> String {
+        let signature: String = Self::fn_signature(&f);
+        let pointers: Vec<String> = f.captures.iter().map(|c| c.name.clone()).collect();
+        let outer: Vec<String> = std::mem::replace(&mut self.pointers, pointers);
 
         let mut children: Vec<String> = Vec::new();
         f.children.into_iter().for_each(|child: Box<NodeType>| {
@@ -189,46 +569,257 @@ impl CLang {
             (!stmt_string.is_empty()).then(|| children.push(stmt_string));
         });
 
+        self.pointers = outer;
+
+        format!("{} {{\n{}\n}}", signature, children.join("\n"))
+    }
+
+    /// Moves type definitions out of `children` and declares its functions, so they
+    /// can be called before their definitions
+    #[allow(clippy::vec_box)]
+    fn compile_declarations(children: &mut Vec<Box<NodeType>>) -> String {
+        let mut declarations: String = String::new();
+
+        children.retain(|child| match child.as_ref() {
+            NodeType::DEFINITION(DefinitionType::Struct(sds)) => {
+                declarations.push_str(&Self::compile_struct(sds.clone()));
+                declarations.push('\n');
+                false
+            }
+            NodeType::DEFINITION(DefinitionType::Enum(eds)) => {
+                declarations.push_str(&Self::compile_enum(eds.clone()));
+                declarations.push('\n');
+                false
+            }
+            _ => true,
+        });
+
+        children.iter().for_each(|child| match child.as_ref() {
+            NodeType::DEFINITION(DefinitionType::Function(fds))
+                if fds.must_be_compiled && fds.generics.is_empty() =>
+            {
+                declarations.push_str(&format!("{};\n", Self::fn_signature(fds)));
+            }
+            _ => {}
+        });
+
+        declarations
+    }
+
+    fn fn_signature(f: &FunctionDefinitionStruct) -> String {
+        let mut args: Vec<String> = f.args.iter().map(Self::c_arg).collect::<Vec<String>>();
+
+        // Captured variables follow the arguments
+        args.extend(f.captures.iter().map(Self::capture_param));
+
+        format!(
+            "{} {}({})",
+            Self::convert_types(f.data_type.clone()),
+            f.name,
+            args.join(", ")
+        )
+    }
+
+    fn c_arg(arg: &ArgStruct) -> String {
         format!(
-            "{} {}({}) {{\n{}\n}}",
-            Self::convert_types(f.data_type),
-            fn_name,
-            args.join(", "),
-            children.join("\n")
+            "{} {}",
+            Self::convert_types(arg.data_type.clone()),
+            arg.name
+        )
+    }
+
+    fn capture_param(capture: &CaptureStruct) -> String {
+        let is_const: &str = if capture.is_const { "const " } else { "" };
+        match &capture.data_type {
+            DataType::Array(element_type, len) => format!(
+                "{}{} (*{})[{}]",
+                is_const,
+                Self::convert_types(*element_type.clone()),
+                capture.name,
+                len
+            ),
+            data_type => format!(
+                "{}{} *{}",
+                is_const,
+                Self::convert_types(data_type.clone()),
+                capture.name
+            ),
+        }
+    }
+
+    /// Wraps a function used as a value. `h8_call_*` takes the environment and the
+    /// arguments in declared order, `h8_make_*` copies the captured variables
+    /// into a new environment
+    fn compile_closure(f: &FunctionDefinitionStruct) -> String {
+        let env: String = format!("h8_env_{}", f.name);
+
+        let mut call_args: Vec<String> = f.args.iter().map(|arg| arg.name.clone()).collect();
+        call_args.extend(f.captures.iter().map(|c| format!("&h8_e->{}", c.name)));
+        let call: String = format!("{}({});", f.name, call_args.join(", "));
+
+        let params: String = f
+            .args
+            .iter()
+            .map(|arg: &ArgStruct| format!(", {}", Self::c_arg(arg)))
+            .collect();
+        let body: String = match f.data_type {
+            DataType::Void => call,
+            _ => format!("return {}", call),
+        };
+
+        let make_params: Vec<String> = f.captures.iter().map(Self::capture_param).collect();
+        let wrapper: String = format!("(void (*)(void))h8_call_{}", f.name);
+
+        match f.captures.is_empty() {
+            true => format!(
+                "static {} h8_call_{}(void *h8_env{}) {{
+(void)h8_env;
+{}
+}}
+static h8_closure h8_make_{}(void) {{
+return (h8_closure){{ {}, NULL }};
+}}",
+                Self::convert_types(f.data_type.clone()),
+                f.name,
+                params,
+                body,
+                f.name,
+                wrapper
+            ),
+            false => {
+                let fields: String = f
+                    .captures
+                    .iter()
+                    .map(|c: &CaptureStruct| match &c.data_type {
+                        DataType::Array(element_type, len) => format!(
+                            "{} {}[{}];
+",
+                            Self::convert_types(*element_type.clone()),
+                            c.name,
+                            len
+                        ),
+                        data_type => {
+                            format!(
+                                "{} {};
+",
+                                Self::convert_types(data_type.clone()),
+                                c.name
+                            )
+                        }
+                    })
+                    .collect();
+                let copies: String = f
+                    .captures
+                    .iter()
+                    .map(|c: &CaptureStruct| {
+                        format!(
+                            "memcpy(&h8_e->{0}, {0}, sizeof h8_e->{0});
+",
+                            c.name
+                        )
+                    })
+                    .collect();
+                format!(
+                    "typedef struct {{
+{}}} {};
+static {} h8_call_{}(void *h8_env{}) {{
+{} *h8_e = h8_env;
+{}
+}}
+static h8_closure h8_make_{}({}) {{
+{} *h8_e = h8_env_new(sizeof *h8_e);
+{}return (h8_closure){{ {}, h8_e }};
+}}",
+                    fields,
+                    env,
+                    Self::convert_types(f.data_type.clone()),
+                    f.name,
+                    params,
+                    env,
+                    body,
+                    f.name,
+                    make_params.join(", "),
+                    env,
+                    copies,
+                    wrapper
+                )
+            }
+        }
+    }
+
+    /// Function values are called through their `h8_call_*` wrapper
+    fn compile_value_call(call: CallStruct, args: Vec<DataType>, data_type: DataType) -> String {
+        let fn_type: String = format!(
+            "{} (*)(void *{})",
+            Self::convert_types(data_type),
+            args.into_iter()
+                .map(|arg: DataType| format!(", {}", Self::convert_types(arg)))
+                .collect::<String>()
+        );
+        let values: String = call
+            .args
+            .into_iter()
+            .map(|a| format!(", {}", Self::process_expr_token(a.value.unwrap())))
+            .collect();
+
+        format!(
+            "(({}){}.fn)({}.env{});",
+            fn_type, call.calling_name, call.calling_name, values
         )
     }
 
     fn compile_call(&mut self, call: CallStruct) -> String {
-        let calling_name = is_c_keyword(&call.calling_name)
-            .then(|| format!("{}_func", call.calling_name))
-            .unwrap_or_else(|| call.calling_name.clone());
+        if let Some(DataType::Fn(args, data_type)) = call.callee.clone() {
+            return Self::compile_value_call(call, args, *data_type);
+        }
+        if let Some(list_call) = Std::compile_list_call(&call) {
+            return list_call;
+        }
+        if let Some(conversion) = Std::compile_conversion(&call) {
+            return conversion;
+        }
 
+        let calling_name = if !call.type_args.is_empty() {
+            self.instance(&call.calling_name, call.type_args.clone())
+        } else {
+            call.calling_name.clone()
+        };
+
+        // Arguments are already in declaration order, see `check_call_args`
         let mut args: Vec<String> = call
             .args
             .iter()
             .map(|a| Self::process_expr_token(a.value.clone().unwrap()))
             .collect::<Vec<String>>();
-        args.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()));
+
+        // Captured variables are passed by pointer, or as is when they are already pointers
+        if let Some(captures) = self.captures.get(&call.calling_name) {
+            args.extend(
+                captures
+                    .iter()
+                    .map(|name: &String| match self.pointers.contains(name) {
+                        true => name.clone(),
+                        false => format!("&{}", name),
+                    }),
+            );
+        }
 
         format!("{}({});", calling_name, args.join(", "))
     }
 
     fn compile_assign(&mut self, assign_struct: AssignStruct) -> String {
+        let target: String = match assign_struct.element {
+            Some(element) => Self::process_expr_token(element),
+            None => assign_struct.name,
+        };
+
         match assign_struct.body {
             AssignEnum::Expr(expr_token) => {
-                format!(
-                    "{} = {};",
-                    assign_struct.name,
-                    Self::process_expr_token(expr_token)
-                )
+                format!("{} = {};", target, Self::process_expr_token(expr_token))
             }
             AssignEnum::Call(node_type) => match *node_type {
                 NodeType::CALL(call_struct) => {
-                    format!(
-                        "{} = {};",
-                        assign_struct.name,
-                        self.compile_call(call_struct)
-                    )
+                    format!("{} = {};", target, self.compile_call(call_struct))
                 }
                 _ => unreachable!(),
             },
@@ -238,9 +829,23 @@ impl CLang {
 
     pub fn process_expr_token(token: ExprToken) -> String {
         match token {
+            ExprToken::Number(n) if n > i64::MAX as i128 => format!("{}ULL", n),
+            // `9223372036854775808LL` does not fit, so the minimum has to be computed
+            ExprToken::Number(n) if n == i64::MIN as i128 => {
+                format!("({}LL - 1)", i64::MIN + 1)
+            }
+            ExprToken::Number(n) if n > i32::MAX as i128 || n < i32::MIN as i128 => {
+                format!("{}LL", n)
+            }
             ExprToken::Number(n) => format!("{}", n),
             ExprToken::Variable(v) => v.name,
             ExprToken::Literal(l) => format!("\"{}\"", l),
+            ExprToken::Bool(b) => format!("{}", b),
+            ExprToken::Add(l, r) if l.resolved_type() == DataType::Str => {
+                let l_token = Self::process_expr_token(*l);
+                let r_token = Self::process_expr_token(*r);
+                format!("h8_concat({}, {})", l_token, r_token)
+            }
             ExprToken::Add(l, r) => {
                 let l_token = Self::process_expr_token(*l);
                 let r_token = Self::process_expr_token(*r);
@@ -266,21 +871,86 @@ impl CLang {
                 let r_token = Self::process_expr_token(*r);
                 format!("pow({}, {})", l_token, r_token)
             }
+            ExprToken::Cast(v, data_type) => {
+                format!(
+                    "(({}){})",
+                    Self::convert_types(data_type),
+                    Self::process_expr_token(*v)
+                )
+            }
+            ExprToken::Eq(l, r) => Self::compile_comparison(*l, *r, "=="),
+            ExprToken::Ne(l, r) => Self::compile_comparison(*l, *r, "!="),
+            ExprToken::Lt(l, r) => Self::compile_comparison(*l, *r, "<"),
+            ExprToken::Gt(l, r) => Self::compile_comparison(*l, *r, ">"),
+            ExprToken::Le(l, r) => Self::compile_comparison(*l, *r, "<="),
+            ExprToken::Ge(l, r) => Self::compile_comparison(*l, *r, ">="),
+            ExprToken::If(c, a, b) => format!(
+                "({} ? {} : {})",
+                Self::process_expr_token(*c),
+                Self::process_expr_token(*a),
+                Self::process_expr_token(*b)
+            ),
+            ExprToken::Index(array, index, line) => Self::compile_index(*array, *index, line),
+            ExprToken::Field(value, field) => {
+                format!("{}.{}", Self::process_expr_token(*value), field.name)
+            }
+            ExprToken::Struct(name, fields) => format!(
+                "(({}){{{}}})",
+                Self::struct_type(&name),
+                fields
+                    .into_iter()
+                    .map(|(field, value)| format!(
+                        ".{} = {}",
+                        field,
+                        Self::process_expr_token(value)
+                    ))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            ExprToken::Variant(name, value) => Self::enum_value(&name, &value),
         }
     }
 
-    fn random_string(length: usize) -> String {
-        thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(length)
-            .map(char::from)
-            .collect()
+    /// Constant indexes are checked by the compiler, others at runtime
+    fn compile_index(array: ExprToken, index: ExprToken, line: usize) -> String {
+        let len: usize = match array.resolved_type() {
+            DataType::Array(_, len) => len,
+            _ => unreachable!(),
+        };
+        let array_name: String = match &array {
+            // Captured arrays are used through a pointer
+            ExprToken::Variable(v) => v
+                .name
+                .trim_start_matches("(*")
+                .trim_end_matches(')')
+                .to_string(),
+            _ => String::from("array"),
+        };
+        let array_token: String = Self::process_expr_token(array);
+
+        match index {
+            ExprToken::Number(n) => format!("{}[{}]", array_token, n),
+            index => format!(
+                "{}[H8_INDEX({}, {}, \"{}\", {})]",
+                array_token,
+                Self::process_expr_token(index),
+                len,
+                array_name,
+                line
+            ),
+        }
     }
-}
 
-#[inline(always)]
-fn is_c_keyword(name: &str) -> bool {
-    C_KEYWORDS.contains(&name)
+    fn compile_comparison(l: ExprToken, r: ExprToken, op: &str) -> String {
+        let is_str: bool = l.resolved_type() == DataType::Str;
+        let l_token = Self::process_expr_token(l);
+        let r_token = Self::process_expr_token(r);
+
+        match is_str {
+            true => format!("(strcmp({}, {}) {} 0)", l_token, r_token, op),
+            false => format!("{} {} {}", l_token, op, r_token),
+        }
+    }
 }
 
 pub trait CompilerCodegen {
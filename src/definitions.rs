@@ -1,13 +1,18 @@
 use std::collections::{HashMap, HashSet};
 
-use crate::code_tree::types::{ArgStruct, AssignEnum, CallArgStruct, ServiceBlockType};
+use crate::code_tree::is_valid_identifier;
+use crate::code_tree::types::{
+    ArgStruct, AssignEnum, BlockType, CallArgStruct, CallStruct, EnumDefinitionStruct, MatchStruct,
+    ServiceBlockType, StructDefinitionStruct,
+};
 use crate::errors::simple::SimpleError;
 use crate::errors::ErrorKind;
+use crate::libs::std::Std;
 use crate::math::errors::DefinitionNotFound;
 use crate::math::VariableType;
 use crate::{
     code_tree::types::{
-        DefinitionType, FunctionDefinitionStruct, NodeType, VariableDefinitionStruct,
+        DataType, DefinitionType, FunctionDefinitionStruct, NodeType, VariableDefinitionStruct,
     },
     math::ExprToken,
 };
@@ -16,11 +21,73 @@ use crate::{
 pub enum Defined {
     Variable(VariableDefinitionStruct),
     Function(FunctionDefinitionStruct),
+    Overloads(Vec<FunctionDefinitionStruct>), // Functions of one block sharing a name
+    Struct(StructDefinitionStruct),
+    Enum(EnumDefinitionStruct),
+    TypeParam, // Type parameter of the enclosing generic function
+}
+
+/// Whether two functions take the same argument types
+pub fn same_args(a: &FunctionDefinitionStruct, b: &FunctionDefinitionStruct) -> bool {
+    a.args.len() == b.args.len()
+        && a.args
+            .iter()
+            .zip(&b.args)
+            .all(|(x, y)| same_type(&x.data_type, &y.data_type))
+}
+
+/// User types and type parameters may not be resolved yet, so they are compared by name
+fn same_type(a: &DataType, b: &DataType) -> bool {
+    match (a, b) {
+        (
+            DataType::Struct(a) | DataType::Enum(a) | DataType::Generic(a),
+            DataType::Struct(b) | DataType::Enum(b) | DataType::Generic(b),
+        ) => a == b,
+        (DataType::List(a), DataType::List(b)) => same_type(a, b),
+        (DataType::Array(a, a_len), DataType::Array(b, b_len)) => a_len == b_len && same_type(a, b),
+        (DataType::Fn(a_args, a), DataType::Fn(b_args, b)) => {
+            a_args.len() == b_args.len()
+                && a_args.iter().zip(b_args).all(|(a, b)| same_type(a, b))
+                && same_type(a, b)
+        }
+        (a, b) => a == b,
+    }
+}
+
+/// Adds a function to the scope, replacing its previous version among the overloads
+pub fn declare_function(scope: &mut HashMap<String, Defined>, fds: FunctionDefinitionStruct) {
+    let name: String = fds.name.clone();
+    let defined: Defined = match scope.remove(&name) {
+        Some(Defined::Function(f)) if !same_args(&f, &fds) => Defined::Overloads(vec![f, fds]),
+        Some(Defined::Overloads(mut overloads)) => {
+            match overloads.iter().position(|f| same_args(f, &fds)) {
+                Some(i) => overloads[i] = fds,
+                None => overloads.push(fds),
+            }
+            Defined::Overloads(overloads)
+        }
+        _ => Defined::Function(fds),
+    };
+    scope.insert(name, defined);
 }
 
-pub fn start_def_check(tree: &mut NodeType) {
+/// `<head>` starts with `imported` std and imported definitions. The `hidden` ones among
+/// them come from modules imported by other modules, and only those modules can use them
+pub fn start_def_check(tree: &mut NodeType, imported: usize, hidden: &HashSet<String>) {
+    let NodeType::BLOCK(html) = tree else {
+        unreachable!()
+    };
     let mut defined: HashMap<String, Defined> = HashMap::new();
-    check(tree, &mut defined);
+    html.children
+        .iter_mut()
+        .for_each(|block: &mut Box<NodeType>| match block.as_mut() {
+            // Definitions in `<head>` are visible to the whole program
+            NodeType::BLOCK(head) if head.tag == BlockType::Head => {
+                check_visible(&mut head.children, &mut defined, imported, hidden);
+                head.children.iter().for_each(|child| check_global(child));
+            }
+            block => check(block, &mut defined, false),
+        });
 }
 
 fn find_duplicate<T: Eq + std::hash::Hash + Clone>(arr: &[T]) -> Option<T> {
@@ -45,20 +112,164 @@ fn check_duplicate_def(args: Vec<String>, calling_name: String) {
     }
 }
 
-fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
+/// Signatures of the functions declared in a list of statements.
+/// Functions of the list sharing a name are overloads, told apart by argument types
+/// Whether `name` is defined by the program, std library functions can be shadowed
+fn is_taken(defined: &HashMap<String, Defined>, name: &str) -> bool {
+    match defined.get(name) {
+        Some(Defined::Function(fds)) => !Std::is_library(fds),
+        Some(Defined::Overloads(overloads)) => !overloads.iter().all(Std::is_library),
+        other => other.is_some(),
+    }
+}
+
+pub fn block_functions(children: &[Box<NodeType>]) -> HashMap<String, Defined> {
+    let mut functions: HashMap<String, Defined> = HashMap::new();
+    children.iter().for_each(|child| {
+        if let NodeType::DEFINITION(DefinitionType::Function(fds)) = child.as_ref() {
+            let same: bool = match functions.get(&fds.name) {
+                Some(Defined::Function(f)) => same_args(f, fds),
+                Some(Defined::Overloads(overloads)) => overloads.iter().any(|f| same_args(f, fds)),
+                _ => false,
+            };
+            same.then(|| {
+                SimpleError::error(
+                    &format!(
+                        "Cannot redefine function `{}` with the same argument types",
+                        fds.name
+                    ),
+                    ErrorKind::DefinitionCheck,
+                )
+            });
+            declare_function(&mut functions, fds.clone());
+        }
+    });
+    functions
+}
+
+/// Checks a list of statements. Function bodies see the signatures of all
+/// functions in the list, so they can be recursive and mutually recursive
+fn check_children(
+    children: &mut [Box<NodeType>],
+    defined: &mut HashMap<String, Defined>,
+    in_loop: bool,
+) {
+    let len: usize = children.len();
+    check_statements(children, defined, in_loop, len, &HashSet::new());
+}
+
+/// Checks the top level of `<head>`, where the program's own definitions cannot see `hidden` names
+fn check_visible(
+    children: &mut [Box<NodeType>],
+    defined: &mut HashMap<String, Defined>,
+    imported: usize,
+    hidden: &HashSet<String>,
+) {
+    check_statements(children, defined, false, imported, hidden);
+    defined.retain(|name: &String, _| !hidden.contains(name));
+}
+
+/// Statements from index `own` on cannot see the `hidden` names
+fn check_statements(
+    children: &mut [Box<NodeType>],
+    defined: &mut HashMap<String, Defined>,
+    in_loop: bool,
+    own: usize,
+    hidden: &HashSet<String>,
+) {
+    let functions: HashMap<String, Defined> = block_functions(children);
+
+    let mut declared: HashSet<String> = HashSet::new();
+    children.iter_mut().enumerate().for_each(|(index, child)| {
+        if index == own {
+            defined.retain(|name: &String, _| !hidden.contains(name));
+        }
+        let NodeType::DEFINITION(DefinitionType::Function(fds)) = child.as_ref() else {
+            return check(child, defined, in_loop);
+        };
+        let name: String = fds.name.clone();
+
+        (is_taken(defined, &name) && !declared.contains(&name)).then(|| {
+            SimpleError::error(
+                &format!("Cannot redefine function `{}`", name),
+                ErrorKind::DefinitionCheck,
+            );
+        });
+        declared.insert(name.clone());
+
+        let mut scope: HashMap<String, Defined> = defined.clone();
+        scope.extend(
+            functions
+                .clone()
+                .into_iter()
+                .filter(|(name, _)| index < own || !hidden.contains(name)),
+        );
+        check(child, &mut scope, in_loop);
+        defined.insert(name.clone(), scope.remove(&name).unwrap());
+    });
+}
+
+fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>, in_loop: bool) {
     match tree {
         NodeType::BLOCK(block_struct) => {
             let scope = defined.clone();
-            block_struct
-                .children
-                .iter_mut()
-                .for_each(|child: &mut Box<NodeType>| {
-                    check(child, defined);
-                });
-            *defined = scope.clone();
+            check_children(&mut block_struct.children, defined, in_loop);
+            *defined = scope;
         }
         NodeType::DEFINITION(definition_type) => match definition_type {
             DefinitionType::Function(fds) => {
+                // Nested functions are moved to file scope, where type parameters are unknown
+                defined
+                    .values()
+                    .any(|def| matches!(def, Defined::TypeParam))
+                    .then(|| {
+                        SimpleError::error(
+                            &format!(
+                                "Function `{}` cannot be declared inside a generic function",
+                                fds.name
+                            ),
+                            ErrorKind::DefinitionCheck,
+                        )
+                    });
+
+                // Type parameters and arguments are only visible inside the function
+                let scope = defined.clone();
+                fds.generics.iter().for_each(|name: &String| {
+                    defined.insert(name.clone(), Defined::TypeParam);
+                });
+
+                resolve_type(&mut fds.data_type, defined);
+                fds.args.iter_mut().for_each(|arg: &mut ArgStruct| {
+                    resolve_type(&mut arg.data_type, defined);
+                    if let Some(default) = &arg.default {
+                        default
+                            .check_def(defined)
+                            .unwrap_or_else(|e: DefinitionNotFound| {
+                                SimpleError::error(
+                                    &format!("Variable `{}` not defined", e.var_name),
+                                    ErrorKind::DefinitionCheck,
+                                )
+                            });
+                    }
+                });
+
+                // Type parameters are inferred from the arguments of a call
+                fds.generics.iter().for_each(|name: &String| {
+                    let unknown = HashMap::from([(name.clone(), DataType::Void)]);
+                    fds.args
+                        .iter()
+                        .all(|arg: &ArgStruct| arg.data_type.substitute(&unknown) == arg.data_type)
+                        .then(|| {
+                            SimpleError::error(
+                                &format!(
+                                    "Type parameter `{}` of `{}` is not used by its arguments",
+                                    name, fds.name
+                                ),
+                                ErrorKind::DefinitionCheck,
+                            )
+                        });
+                });
+
                 fds.args.clone().into_iter().for_each(|arg: ArgStruct| {
                     let var = Defined::Variable(VariableDefinitionStruct {
                         data_type: arg.data_type.clone(),
@@ -79,26 +290,15 @@ fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
                     fds.name.clone(),
                 );
 
-                let scope = defined.clone();
-                fds.children
-                    .iter_mut()
-                    .for_each(|child: &mut Box<NodeType>| {
-                        check(child, defined);
-                    });
+                check_children(&mut fds.children, defined, false);
 
-                *defined = scope.clone();
+                *defined = scope;
 
-                defined.get(&fds.name).is_some().then(|| {
-                    SimpleError::error(
-                        &format!("Cannot redefine function `{}`", fds.name),
-                        ErrorKind::DefinitionCheck,
-                    );
-                });
-
-                defined.insert(fds.name.clone(), Defined::Function(fds.clone()));
+                declare_function(defined, fds.clone());
             }
             DefinitionType::Variable(vds) => {
-                match &vds.value {
+                resolve_type(&mut vds.data_type, defined);
+                match &mut vds.value {
                     AssignEnum::Expr(expr_token) => {
                         expr_token
                             .check_def(defined)
@@ -109,9 +309,9 @@ fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
                                 )
                             });
                     }
-                    AssignEnum::Call(node_type) => match *node_type.clone() {
-                        NodeType::CALL(mut call_struct) => {
-                            check_fn_call(defined, &mut call_struct);
+                    AssignEnum::Call(node_type) => match node_type.as_mut() {
+                        NodeType::CALL(call_struct) => {
+                            check_fn_call(defined, call_struct);
 
                             defined.get(&call_struct.calling_name).unwrap_or_else(|| {
                                 SimpleError::error(
@@ -125,10 +325,10 @@ fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
                             ErrorKind::DefinitionCheck,
                         ),
                     },
-                    AssignEnum::None => unreachable!(),
+                    AssignEnum::None => {} // Empty list
                 }
 
-                defined.get(&vds.name).is_some().then(|| {
+                is_taken(defined, &vds.name).then(|| {
                     SimpleError::error(
                         &format!("Cannot redefine variable `{}`", vds.name),
                         ErrorKind::DefinitionCheck,
@@ -137,14 +337,94 @@ fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
 
                 defined.insert(vds.name.clone(), Defined::Variable(vds.clone()));
             }
+            DefinitionType::Array(ads) => {
+                resolve_type(&mut ads.element_type, defined);
+                ads.len
+                    .check_def(defined)
+                    .unwrap_or_else(|e: DefinitionNotFound| {
+                        SimpleError::error(
+                            &format!("Variable `{}` not defined", e.var_name),
+                            ErrorKind::DefinitionCheck,
+                        )
+                    });
+
+                is_taken(defined, &ads.name).then(|| {
+                    SimpleError::error(
+                        &format!("Cannot redefine variable `{}`", ads.name),
+                        ErrorKind::DefinitionCheck,
+                    );
+                });
+
+                defined.insert(ads.name.clone(), untyped_variable(&ads.name));
+            }
+            DefinitionType::Struct(sds) => {
+                check_duplicate_def(
+                    sds.fields.iter().map(|f| f.name.clone()).collect(),
+                    sds.name.clone(),
+                );
+                sds.fields
+                    .iter_mut()
+                    .for_each(|field: &mut ArgStruct| resolve_type(&mut field.data_type, defined));
+
+                is_taken(defined, &sds.name).then(|| {
+                    SimpleError::error(
+                        &format!("Cannot redefine `{}`", sds.name),
+                        ErrorKind::DefinitionCheck,
+                    );
+                });
+
+                defined.insert(sds.name.clone(), Defined::Struct(sds.clone()));
+            }
+            DefinitionType::Enum(eds) => {
+                check_duplicate_def(eds.values.clone(), eds.name.clone());
+
+                is_taken(defined, &eds.name).then(|| {
+                    SimpleError::error(
+                        &format!("Cannot redefine `{}`", eds.name),
+                        ErrorKind::DefinitionCheck,
+                    );
+                });
+
+                defined.insert(eds.name.clone(), Defined::Enum(eds.clone()));
+            }
         },
-        NodeType::CALL(call_struct) => check_fn_call(defined, call_struct),
+        NodeType::CALL(call_struct)
+            if matches!(
+                defined.get(&call_struct.calling_name),
+                Some(Defined::Struct(_))
+            ) =>
+        {
+            let vds: VariableDefinitionStruct = match defined.get(&call_struct.calling_name) {
+                Some(Defined::Struct(sds)) => build_struct_variable(call_struct, sds),
+                _ => unreachable!(),
+            };
+            *tree = NodeType::DEFINITION(DefinitionType::Variable(vds));
+            check(tree, defined, in_loop);
+        }
+        NodeType::CALL(call_struct) => {
+            if !in_loop && ["break", "continue"].contains(&call_struct.calling_name.as_str()) {
+                SimpleError::error(
+                    &format!(
+                        "`<{}>` can only be used inside a loop",
+                        call_struct.calling_name
+                    ),
+                    ErrorKind::DefinitionCheck,
+                );
+            }
+            check_fn_call(defined, call_struct)
+        }
         NodeType::ASSIGN(ref mut call_arg_struct) => {
             match defined.get(&call_arg_struct.name) {
-                Some(Defined::Function(_)) => SimpleError::error(
+                Some(Defined::Function(_) | Defined::Overloads(_)) => SimpleError::error(
                     &format!("Cannot assign value to `{}` function", call_arg_struct.name),
                     ErrorKind::DefinitionCheck,
                 ),
+                Some(Defined::Struct(_) | Defined::Enum(_) | Defined::TypeParam) => {
+                    SimpleError::error(
+                        &format!("Cannot assign value to `{}` type", call_arg_struct.name),
+                        ErrorKind::DefinitionCheck,
+                    )
+                }
                 Some(Defined::Variable(v)) => {
                     if v.is_const {
                         SimpleError::error(
@@ -165,7 +445,18 @@ fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
                 ),
             };
 
-            match call_arg_struct.body.clone() {
+            if let Some(element) = &call_arg_struct.element {
+                element
+                    .check_def(defined)
+                    .unwrap_or_else(|e: DefinitionNotFound| {
+                        SimpleError::error(
+                            &format!("Variable `{}` not defined", e.var_name),
+                            ErrorKind::DefinitionCheck,
+                        )
+                    });
+            }
+
+            match &mut call_arg_struct.body {
                 AssignEnum::Expr(expr_token) => {
                     expr_token
                         .check_def(defined)
@@ -176,8 +467,8 @@ fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
                             )
                         })
                 }
-                AssignEnum::Call(mut body) => match *body.clone() {
-                    NodeType::CALL(_) => check(&mut body, defined),
+                AssignEnum::Call(body) => match body.as_ref() {
+                    NodeType::CALL(_) => check(body, defined, in_loop),
                     _ => SimpleError::error(
                         &format!("Unexpected token inside `{}` assign", call_arg_struct.name),
                         ErrorKind::DefinitionCheck,
@@ -190,7 +481,7 @@ fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
             ServiceBlockType::For(for_struct) => {
                 for_struct
                     .start
-                    .check_def(&defined)
+                    .check_def(defined)
                     .unwrap_or_else(|e: DefinitionNotFound| {
                         SimpleError::error(
                             &format!("Variable `{}` not defined", e.var_name),
@@ -200,7 +491,7 @@ fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
 
                 for_struct
                     .end
-                    .check_def(&defined)
+                    .check_def(defined)
                     .unwrap_or_else(|e: DefinitionNotFound| {
                         SimpleError::error(
                             &format!("Variable `{}` not defined", e.var_name),
@@ -208,19 +499,319 @@ fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
                         )
                     });
 
-                let scope = defined.clone();
                 for_struct
-                    .children
-                    .iter_mut()
-                    .for_each(|child: &mut Box<NodeType>| {
-                        check(child, defined);
+                    .step
+                    .check_def(defined)
+                    .unwrap_or_else(|e: DefinitionNotFound| {
+                        SimpleError::error(
+                            &format!("Variable `{}` not defined", e.var_name),
+                            ErrorKind::DefinitionCheck,
+                        )
                     });
+
+                let scope = defined.clone();
+                check_children(&mut for_struct.children, defined, true);
+                *defined = scope.clone();
+            }
+            ServiceBlockType::ForEach(for_each_struct) => {
+                for_each_struct.iterable.check_def(defined).unwrap_or_else(
+                    |e: DefinitionNotFound| {
+                        SimpleError::error(
+                            &format!("Variable `{}` not defined", e.var_name),
+                            ErrorKind::DefinitionCheck,
+                        )
+                    },
+                );
+
+                let scope = defined.clone();
+                defined.insert(
+                    for_each_struct.item_name.clone(),
+                    untyped_variable(&for_each_struct.item_name),
+                );
+                check_children(&mut for_each_struct.children, defined, true);
+                *defined = scope.clone();
+            }
+            ServiceBlockType::Match(match_struct) => check_match(match_struct, defined, in_loop),
+            ServiceBlockType::While(while_struct) => {
+                while_struct.condition.check_def(defined).unwrap_or_else(
+                    |e: DefinitionNotFound| {
+                        SimpleError::error(
+                            &format!("Variable `{}` not defined", e.var_name),
+                            ErrorKind::DefinitionCheck,
+                        )
+                    },
+                );
+
+                let scope = defined.clone();
+                check_children(&mut while_struct.children, defined, true);
                 *defined = scope.clone();
             }
+            ServiceBlockType::If(if_struct) => {
+                let scope = defined.clone();
+                if_struct.branches.iter_mut().for_each(|branch| {
+                    branch
+                        .condition
+                        .check_def(defined)
+                        .unwrap_or_else(|e: DefinitionNotFound| {
+                            SimpleError::error(
+                                &format!("Variable `{}` not defined", e.var_name),
+                                ErrorKind::DefinitionCheck,
+                            )
+                        });
+
+                    check_children(&mut branch.children, defined, in_loop);
+                    *defined = scope.clone();
+                });
+
+                if let Some(children) = &mut if_struct.else_children {
+                    check_children(children, defined, in_loop);
+                    *defined = scope.clone();
+                }
+            }
         },
     }
 }
 
+/// Each case of `<match>` is checked in its own scope
+fn check_match(
+    match_struct: &mut MatchStruct,
+    defined: &mut HashMap<String, Defined>,
+    in_loop: bool,
+) {
+    match_struct
+        .value
+        .check_def(defined)
+        .unwrap_or_else(|e: DefinitionNotFound| {
+            SimpleError::error(
+                &format!("Variable `{}` not defined", e.var_name),
+                ErrorKind::DefinitionCheck,
+            )
+        });
+
+    let scope = defined.clone();
+    match_struct
+        .cases
+        .iter_mut()
+        .map(|case| &mut case.children)
+        .chain(match_struct.default.iter_mut())
+        .for_each(|children| {
+            check_children(children, defined, in_loop);
+            *defined = scope.clone();
+        });
+}
+
+/// Only declarations that can live at file scope are allowed in `<head>`
+fn check_global(node: &NodeType) {
+    match node {
+        NodeType::DEFINITION(DefinitionType::Variable(vds)) if !vds.is_const => SimpleError::error(
+            &format!("Variable `{}` in `<head>` must be `const`", vds.name),
+            ErrorKind::DefinitionCheck,
+        ),
+        NodeType::DEFINITION(DefinitionType::Array(ads)) => SimpleError::error(
+            &format!("Array `{}` cannot be declared in `<head>`", ads.name),
+            ErrorKind::DefinitionCheck,
+        ),
+        NodeType::DEFINITION(_) => {}
+        _ => SimpleError::error(
+            "Only functions, `const` variables, structs and enums can be declared in `<head>`",
+            ErrorKind::DefinitionCheck,
+        ),
+    }
+}
+
+/// Checks that a user type exists and tells structs and enums apart
+fn resolve_type(data_type: &mut DataType, defined: &HashMap<String, Defined>) {
+    match data_type {
+        DataType::Struct(name) => match defined.get(name) {
+            Some(Defined::Struct(_)) => {}
+            Some(Defined::Enum(_)) => *data_type = DataType::Enum(name.clone()),
+            Some(Defined::TypeParam) => *data_type = DataType::Generic(name.clone()),
+            _ => SimpleError::error(
+                &format!("Unknown type `{}`", name),
+                ErrorKind::DefinitionCheck,
+            ),
+        },
+        DataType::Array(element_type, _) | DataType::List(element_type) => {
+            resolve_type(element_type, defined)
+        }
+        DataType::Fn(args, data_type) => {
+            args.iter_mut()
+                .for_each(|arg: &mut DataType| resolve_type(arg, defined));
+            resolve_type(data_type, defined);
+        }
+        _ => {}
+    }
+}
+
+/// Turns a struct construction like `<Point name="p" x={1} y={2} />` into a variable definition
+fn build_struct_variable(
+    call_struct: &CallStruct,
+    sds: &StructDefinitionStruct,
+) -> VariableDefinitionStruct {
+    check_duplicate_def(
+        call_struct.args.iter().map(|a| a.name.clone()).collect(),
+        sds.name.clone(),
+    );
+
+    let name: String = match call_struct.args.iter().find(|a| a.name == "name") {
+        Some(CallArgStruct {
+            value: Some(ExprToken::Literal(name)),
+            ..
+        }) if is_valid_identifier(name) => name.clone(),
+        Some(_) => SimpleError::error(
+            &format!("Invalid name for `{}` variable", sds.name),
+            ErrorKind::DefinitionCheck,
+        ),
+        None => SimpleError::error(
+            &format!("You should define name for `{}` variable!", sds.name),
+            ErrorKind::DefinitionCheck,
+        ),
+    };
+
+    call_struct
+        .args
+        .iter()
+        .filter(|a| a.name != "name" && a.name != "const")
+        .for_each(|arg: &CallArgStruct| {
+            (!sds.fields.iter().any(|f| f.name == arg.name)).then(|| {
+                SimpleError::error(
+                    &format!("Struct `{}` has no field `{}`", sds.name, arg.name),
+                    ErrorKind::DefinitionCheck,
+                )
+            });
+        });
+
+    let fields: Vec<(String, ExprToken)> = sds
+        .fields
+        .iter()
+        .map(|field: &ArgStruct| {
+            let value: ExprToken = call_struct
+                .args
+                .iter()
+                .find(|a| a.name == field.name)
+                .and_then(|a| a.value.clone())
+                .unwrap_or_else(|| {
+                    SimpleError::error(
+                        &format!(
+                            "Field `{}` is required to create `{}` variable `{}`",
+                            field.name, sds.name, name
+                        ),
+                        ErrorKind::DefinitionCheck,
+                    )
+                });
+            (field.name.clone(), value)
+        })
+        .collect();
+
+    VariableDefinitionStruct {
+        data_type: DataType::Struct(sds.name.clone()),
+        name,
+        value: AssignEnum::Expr(ExprToken::Struct(sds.name.clone(), fields)),
+        is_const: call_struct
+            .args
+            .iter()
+            .any(|a| a.name == "const" && a.value.is_none()),
+    }
+}
+
+/// Variable whose type is only known after the type check
+fn untyped_variable(name: &str) -> Defined {
+    Defined::Variable(VariableDefinitionStruct {
+        data_type: DataType::Any,
+        name: name.to_string(),
+        value: AssignEnum::None,
+        is_const: false,
+    })
+}
+
+/// Why positional values cannot be bound to the parameters of a function
+pub enum BindError {
+    Ambiguous,      // More than one value for a function with an argument named `arg`
+    TooMany(usize), // More values than parameters
+    Twice(String),  // Parameter given both by position and by name
+}
+
+/// Parameter each call argument is passed to, in call order, or `None` for unknown names.
+/// Positional values, which the parser names `arg`, take the parameters at their positions
+pub fn match_params<'a>(
+    call_struct: &CallStruct,
+    f: &'a FunctionDefinitionStruct,
+) -> Result<Vec<Option<&'a ArgStruct>>, BindError> {
+    let by_name = |name: &str| f.args.iter().find(|a: &&ArgStruct| a.name == name);
+    let positional: usize = call_struct
+        .args
+        .iter()
+        .filter(|a: &&CallArgStruct| a.name == "arg")
+        .count();
+
+    // A bare value is passed to a parameter named `arg` as before
+    if by_name("arg").is_some() {
+        if positional > 1 {
+            return Err(BindError::Ambiguous);
+        }
+        return Ok(call_struct
+            .args
+            .iter()
+            .map(|a: &CallArgStruct| by_name(&a.name))
+            .collect());
+    }
+
+    if positional > f.args.len() {
+        return Err(BindError::TooMany(positional));
+    }
+
+    let mut position: usize = 0;
+    call_struct
+        .args
+        .iter()
+        .map(|a: &CallArgStruct| {
+            if a.name != "arg" {
+                return Ok(by_name(&a.name));
+            }
+            let param: &ArgStruct = &f.args[position];
+            position += 1;
+            match call_struct.args.iter().any(|b| b.name == param.name) {
+                true => Err(BindError::Twice(param.name.clone())),
+                false => Ok(Some(param)),
+            }
+        })
+        .collect()
+}
+
+/// Gives positional values the names of the parameters at their positions
+pub fn bind_positional(call_struct: &mut CallStruct, f: &FunctionDefinitionStruct) {
+    let params: Vec<Option<&ArgStruct>> =
+        match_params(call_struct, f).unwrap_or_else(|e: BindError| {
+            let message: String = match e {
+                BindError::Ambiguous => format!(
+                    "Positional values for `{}` are ambiguous, as it has an argument named `arg`",
+                    f.name
+                ),
+                BindError::TooMany(positional) => format!(
+                    "Function `{}` takes {} arguments, got {} positional values",
+                    f.name,
+                    f.args.len(),
+                    positional
+                ),
+                BindError::Twice(name) => format!(
+                    "Argument `{}` of `{}` is given both by position and by name",
+                    name, f.name
+                ),
+            };
+            SimpleError::error(&message, ErrorKind::DefinitionCheck)
+        });
+
+    call_struct
+        .args
+        .iter_mut()
+        .zip(params)
+        .for_each(|(arg, param)| {
+            if let Some(param) = param {
+                arg.name = param.name.clone();
+            }
+        });
+}
+
 fn check_fn_call(
     defined: &mut HashMap<String, Defined>,
     call_struct: &mut crate::code_tree::types::CallStruct,
@@ -237,19 +828,31 @@ fn check_fn_call(
         );
     });
 
-    if let Some(Defined::Variable(vds)) = entry {
-        SimpleError::error(
-            &format!("Cannot call variable as function: {}", vds.name),
-            ErrorKind::DefinitionCheck,
-        );
+    // Calls of function values are checked by the type check, as their arguments have no names
+    match entry {
+        // `<let>` variables get their type later, in the type check
+        Some(Defined::Variable(vds))
+            if !matches!(vds.data_type, DataType::Fn(..) | DataType::Any) =>
+        {
+            SimpleError::error(
+                &format!("Cannot call variable as function: {}", vds.name),
+                ErrorKind::DefinitionCheck,
+            )
+        }
+        _ => {}
     }
 
+    // Overloads are resolved by the type check, which binds their arguments
     if let Some(Defined::Function(f)) = entry {
+        bind_positional(call_struct, f);
+
         f.args.clone().into_iter().for_each(|arg: ArgStruct| {
-            (!call_struct
-                .args
-                .iter()
-                .any(|a: &CallArgStruct| a.name == arg.name))
+            (arg.default.is_none()
+                && !arg.optional
+                && !call_struct
+                    .args
+                    .iter()
+                    .any(|a: &CallArgStruct| a.name == arg.name))
             .then(|| {
                 SimpleError::error(
                     &format!(
@@ -6,4 +6,5 @@ pub enum ErrorKind {
     DefinitionCheck,
     TypeCheck,
     MathProcessing,
+    Import,
 }
@@ -11,6 +11,7 @@ impl SimpleError {
             ErrorKind::DefinitionCheck => String::from("Definition check error"),
             ErrorKind::TypeCheck => String::from("Type check error"),
             ErrorKind::MathProcessing => String::from("Math processing error"),
+            ErrorKind::Import => String::from("Import error"),
         }
     }
 
@@ -22,4 +23,9 @@ impl SimpleError {
         );
         process::exit(-1);
     }
+
+    pub fn warning(mess: &str, error_kind: ErrorKind) {
+        let kind: String = Self::get_error_kind(error_kind).replace("error", "warning");
+        eprintln!("\n{}: {}", kind.yellow().bold(), mess);
+    }
 }
@@ -15,18 +15,15 @@ impl<T: Clone> Iter<T> {
     }
 
     pub fn next(&mut self) -> Option<T> {
-        (self.pos >= self.vec.len())
-            .then_some(None)
-            .unwrap_or_else(|| {
-                self.pos += 1;
-                Some(self.vec[self.pos - 1].clone())
-            })
+        if self.pos >= self.vec.len() {
+            return None;
+        }
+        self.pos += 1;
+        Some(self.vec[self.pos - 1].clone())
     }
 
     pub fn peek(&self) -> Option<T> {
-        (self.pos >= self.vec.len())
-            .then(|| None)
-            .unwrap_or_else(|| Some(self.vec[self.pos].clone()))
+        self.vec.get(self.pos).cloned()
     }
 
     pub fn step_back(&mut self) -> Option<T> {
@@ -7,6 +7,17 @@
     <return {} />
     <inc {} />
     <dec {} />
+    <break />
+    <continue />
+    <push list={} value={} />
+    <pop list={} />
+    <len list={} />
+    <get list={} at={} />
+    <set list={} at={} value={} />
+    <to_str {} />
+    <to_int {} />
+    <to_bool {} />
+    <parse_int {} ok={} />
 */
 
 use crate::{
@@ -15,22 +26,279 @@ use crate::{
         NodeType,
     },
     compiler::CLang,
+    definitions::same_args,
     errors::{simple::SimpleError, ErrorKind},
     math::{ExprToken, VariableType},
 };
 
 pub struct Std;
 
+const INTEGER_TYPES: [DataType; 9] = [
+    DataType::Int,
+    DataType::I8,
+    DataType::I16,
+    DataType::I32,
+    DataType::I64,
+    DataType::U8,
+    DataType::U16,
+    DataType::U32,
+    DataType::U64,
+];
+
+/// C prelude emitted before the program
+const RUNTIME: &str = r#"#include <stdio.h>
+#include <stdlib.h>
+#include <stdbool.h>
+#include <string.h>
+#include <math.h>
+#include <stdint.h>
+#include <inttypes.h>
+#include <ctype.h>
+#include <errno.h>
+#include <limits.h>
+
+static char *h8_concat(const char *a, const char *b) {
+    size_t a_len = strlen(a), b_len = strlen(b);
+    char *result = malloc(a_len + b_len + 1);
+    if (result == NULL) {
+        fprintf(stderr, "Out of memory\n");
+        exit(1);
+    }
+    memcpy(result, a, a_len);
+    memcpy(result + a_len, b, b_len + 1);
+    return result;
+}
+
+#ifdef NDEBUG
+#define H8_INDEX(index, len, name, line) (index)
+#else
+#define H8_INDEX(index, len, name, line) h8_check_index((index), (len), (name), (line))
+static int64_t h8_check_index(int64_t index, int64_t len, const char *name, int line) {
+    if (index < 0 || index >= len) {
+        fprintf(stderr, "Panic at line %d: index %" PRId64 " is out of bounds for `%s` of length %" PRId64 "\n", line, index, name, len);
+        exit(101);
+    }
+    return index;
+}
+#endif
+
+typedef struct {
+    void *data;
+    int64_t len;
+    int64_t cap;
+    size_t item_size;
+} h8_list;
+
+static h8_list *h8_list_new(size_t item_size) {
+    h8_list *list = calloc(1, sizeof(h8_list));
+    if (list == NULL) {
+        fprintf(stderr, "Out of memory\n");
+        exit(1);
+    }
+    list->item_size = item_size;
+    return list;
+}
+
+static void *h8_list_push(h8_list *list) {
+    if (list->len == list->cap) {
+        list->cap = list->cap == 0 ? 4 : list->cap * 2;
+        list->data = realloc(list->data, list->cap * list->item_size);
+        if (list->data == NULL) {
+            fprintf(stderr, "Out of memory\n");
+            exit(1);
+        }
+    }
+    return (char *)list->data + list->item_size * list->len++;
+}
+
+static void *h8_list_pop(h8_list *list, int line) {
+    if (list->len == 0) {
+        fprintf(stderr, "Panic at line %d: cannot pop from an empty list\n", line);
+        exit(101);
+    }
+    return (char *)list->data + list->item_size * --list->len;
+}
+
+static void *h8_list_at(h8_list *list, int64_t index, int line) {
+    if (index < 0 || index >= list->len) {
+        fprintf(stderr, "Panic at line %d: index %" PRId64 " is out of bounds for list of length %" PRId64 "\n", line, index, list->len);
+        exit(101);
+    }
+    return (char *)list->data + list->item_size * index;
+}
+
+/* Function value: a function taking `env` before its arguments */
+typedef struct {
+    void (*fn)(void);
+    void *env;
+} h8_closure;
+
+static void *h8_env_new(size_t size) {
+    void *env = malloc(size);
+    if (env == NULL) {
+        fprintf(stderr, "Out of memory\n");
+        exit(1);
+    }
+    return env;
+}
+
+static char *h8_int_to_str(int64_t value) {
+    char *result = malloc(24);
+    if (result == NULL) {
+        fprintf(stderr, "Out of memory\n");
+        exit(1);
+    }
+    snprintf(result, 24, "%" PRId64, value);
+    return result;
+}
+
+static char *h8_uint_to_str(uint64_t value) {
+    char *result = malloc(24);
+    if (result == NULL) {
+        fprintf(stderr, "Out of memory\n");
+        exit(1);
+    }
+    snprintf(result, 24, "%" PRIu64, value);
+    return result;
+}
+
+/* Parses a whole decimal `int`, setting `ok` to whether the text is one */
+static int h8_parse_int(const char *text, bool *ok) {
+    char *end;
+    long value;
+    errno = 0;
+    value = strtol(text, &end, 10);
+    *ok = end != text && *end == '\0' && !isspace((unsigned char)text[0]) && errno == 0
+        && value >= INT_MIN && value <= INT_MAX;
+    return *ok ? (int)value : 0;
+}"#;
+
+/// Std functions compiled by name, which the program cannot redefine
+const STATEMENTS: &[&str] = &[
+    "println", "print", "return", "inc", "dec", "break", "continue",
+];
+
 impl Std {
+    pub fn runtime() -> &'static str {
+        RUNTIME
+    }
+
+    /// Library functions are left out when the program defines the same name,
+    /// unless it is a function taking other argument types, which overloads them
     #[allow(clippy::vec_box)]
-    pub fn use_lib() -> Vec<Box<NodeType>> {
-        vec![
+    pub fn use_lib(program: &[&DefinitionType]) -> Vec<Box<NodeType>> {
+        let statements: Vec<Box<NodeType>> = vec![
             Box::new(Self::build_println()),
             Box::new(Self::build_print()),
             Box::new(Self::build_return()),
             Box::new(Self::build_inc()),
             Box::new(Self::build_dec()),
-        ]
+            Box::new(Self::build_loop_control("break")),
+            Box::new(Self::build_loop_control("continue")),
+        ];
+        let library = Self::library().into_iter().filter(|node| {
+            let NodeType::DEFINITION(DefinitionType::Function(builtin)) = node.as_ref() else {
+                return true;
+            };
+            !program.iter().any(|definition| match definition {
+                DefinitionType::Function(fds) => {
+                    fds.name == builtin.name && same_args(fds, builtin)
+                }
+                _ => definition.name() == builtin.name,
+            })
+        });
+        statements.into_iter().chain(library).collect()
+    }
+
+    /// Std function that a definition of the program may shadow
+    pub fn is_library(fds: &FunctionDefinitionStruct) -> bool {
+        !fds.must_be_compiled && !STATEMENTS.contains(&fds.name.as_str())
+    }
+
+    #[allow(clippy::vec_box)]
+    fn library() -> Vec<Box<NodeType>> {
+        let mut lib: Vec<Box<NodeType>> = vec![
+            Box::new(Self::build_list_fn("push", DataType::Void, &["value"])),
+            Box::new(Self::build_list_fn("pop", Self::list_item(), &[])),
+            Box::new(Self::build_list_fn("len", DataType::Int, &[])),
+            Box::new(Self::build_list_fn("get", Self::list_item(), &["at"])),
+            Box::new(Self::build_list_fn("set", DataType::Void, &["at", "value"])),
+            Box::new(Self::build_conversion(
+                "to_int",
+                DataType::Int,
+                DataType::Bool,
+            )),
+            Box::new(Self::build_parse_int()),
+        ];
+
+        // Conversions taking several types are overloads, one per type
+        INTEGER_TYPES
+            .into_iter()
+            .chain([DataType::Bool])
+            .for_each(|from: DataType| {
+                lib.push(Box::new(Self::build_conversion(
+                    "to_str",
+                    DataType::Str,
+                    from,
+                )))
+            });
+        INTEGER_TYPES.into_iter().for_each(|from: DataType| {
+            lib.push(Box::new(Self::build_conversion(
+                "to_bool",
+                DataType::Bool,
+                from,
+            )))
+        });
+        lib
+    }
+
+    fn build_conversion(name: &str, data_type: DataType, from: DataType) -> NodeType {
+        NodeType::DEFINITION(DefinitionType::Function(
+            FunctionDefinitionStruct::new_internal(
+                name.to_string(),
+                data_type,
+                vec![ArgStruct::new("arg".to_string(), from)],
+                false,
+            ),
+        ))
+    }
+
+    /// `ok` is a `bool` variable, set to whether the text was an integer
+    fn build_parse_int() -> NodeType {
+        let mut ok: ArgStruct = ArgStruct::new("ok".to_string(), DataType::Bool);
+        ok.writes = true;
+
+        NodeType::DEFINITION(DefinitionType::Function(
+            FunctionDefinitionStruct::new_internal(
+                "parse_int".to_string(),
+                DataType::Int,
+                vec![ArgStruct::new("arg".to_string(), DataType::Str), ok],
+                false,
+            ),
+        ))
+    }
+
+    /// Element type parameter of list functions
+    fn list_item() -> DataType {
+        DataType::Generic(String::from("T"))
+    }
+
+    /// List functions take the list as `list`, an index as `at` and an element as `value`
+    fn build_list_fn(name: &str, data_type: DataType, args: &[&str]) -> NodeType {
+        let mut fn_args: Vec<ArgStruct> = vec![ArgStruct::new(
+            "list".to_string(),
+            DataType::List(Box::new(Self::list_item())),
+        )];
+        args.iter().for_each(|arg: &&str| {
+            fn_args.push(match *arg {
+                "at" => ArgStruct::new(arg.to_string(), DataType::Int),
+                _ => ArgStruct::new(arg.to_string(), Self::list_item()),
+            })
+        });
+
+        NodeType::DEFINITION(DefinitionType::Function(
+            FunctionDefinitionStruct::new_internal(name.to_string(), data_type, fn_args, false),
+        ))
     }
 
     fn build_println() -> NodeType {
@@ -56,11 +324,15 @@ impl Std {
     }
 
     fn build_return() -> NodeType {
+        // `<return />` without a value leaves a void function, see the type check
+        let mut value: ArgStruct = ArgStruct::new("arg".to_string(), DataType::Any);
+        value.optional = true;
+
         NodeType::DEFINITION(DefinitionType::Function(
             FunctionDefinitionStruct::new_internal(
                 "return".to_string(),
                 DataType::Void,
-                vec![ArgStruct::new("arg".to_string(), DataType::Any)],
+                vec![value],
                 false,
             ),
         ))
@@ -71,7 +343,7 @@ impl Std {
             FunctionDefinitionStruct::new_internal(
                 "inc".to_string(),
                 DataType::Void,
-                vec![ArgStruct::new("arg".to_string(), DataType::Int)],
+                vec![ArgStruct::new("arg".to_string(), DataType::Any)],
                 false,
             ),
         ))
@@ -82,26 +354,73 @@ impl Std {
             FunctionDefinitionStruct::new_internal(
                 "dec".to_string(),
                 DataType::Void,
-                vec![ArgStruct::new("arg".to_string(), DataType::Int)],
+                vec![ArgStruct::new("arg".to_string(), DataType::Any)],
                 false,
             ),
         ))
     }
 
-    fn compile_var_println(l: &VariableType, end: String) -> String {
-        let format_key = match l.data_type {
+    fn build_loop_control(name: &str) -> NodeType {
+        NodeType::DEFINITION(DefinitionType::Function(
+            FunctionDefinitionStruct::new_internal(
+                name.to_string(),
+                DataType::Void,
+                Vec::new(),
+                false,
+            ),
+        ))
+    }
+
+    fn format_key(data_type: &DataType) -> String {
+        match data_type {
             DataType::Int => String::from("%d"),
-            DataType::Bool => String::from("%d"),
-            DataType::Str => String::from("%s"),
-            DataType::Void | DataType::Any => unreachable!(),
-        };
-        if !l.is_func {
-            format!("printf(\"{}{}\", {});", format_key, end, l.name)
-        } else {
-            format!("printf(\"<function at %d>{}\", {});", end, l.name)
+            DataType::I8 => String::from("%\" PRId8 \""),
+            DataType::I16 => String::from("%\" PRId16 \""),
+            DataType::I32 => String::from("%\" PRId32 \""),
+            DataType::I64 => String::from("%\" PRId64 \""),
+            DataType::U8 => String::from("%\" PRIu8 \""),
+            DataType::U16 => String::from("%\" PRIu16 \""),
+            DataType::U32 => String::from("%\" PRIu32 \""),
+            DataType::U64 => String::from("%\" PRIu64 \""),
+            DataType::Bool | DataType::Str | DataType::Enum(_) => String::from("%s"),
+            DataType::Fn(..) => String::from("<function>%.0s"),
+            DataType::Array(..) | DataType::List(_) | DataType::Struct(_) => SimpleError::error(
+                &format!("Cannot print value of type `{:?}`", data_type),
+                ErrorKind::TypeCheck,
+            ),
+            DataType::Void | DataType::Any | DataType::Generic(_) => unreachable!(),
+        }
+    }
+
+    /// Wraps a C value so it matches the key returned by [`Std::format_key`]
+    fn format_value(data_type: &DataType, value: String) -> String {
+        match data_type {
+            DataType::Bool => format!("({}) ? \"true\" : \"false\"", value),
+            DataType::Enum(name) => format!("{}[{}]", CLang::enum_names(name), value),
+            DataType::Fn(..) => String::from("\"\""), // Functions print as `<function>`
+            _ => value,
         }
     }
 
+    fn compile_var_println(l: &VariableType, end: String) -> String {
+        format!(
+            "printf(\"{}{}\", {});",
+            Self::format_key(&l.data_type),
+            end,
+            Self::format_value(&l.data_type, l.name.clone())
+        )
+    }
+
+    fn compile_expr_println(expr: &ExprToken, end: String) -> String {
+        let data_type: DataType = expr.resolved_type();
+        format!(
+            "printf(\"{}{}\", {});",
+            Self::format_key(&data_type),
+            end,
+            Self::format_value(&data_type, CLang::process_expr_token(expr.clone()))
+        )
+    }
+
     pub fn compile_println(call: CallStruct) -> String {
         call.args
             .iter()
@@ -109,11 +428,8 @@ impl Std {
             .map(|arg: &CallArgStruct| match &arg.value {
                 Some(ExprToken::Literal(l)) => format!("printf(\"{}\\n\");", l),
                 Some(ExprToken::Variable(l)) => Self::compile_var_println(l, String::from("\\n")),
-                Some(_) => format!(
-                    "printf(\"%d\\n\", {});",
-                    CLang::process_expr_token(arg.value.clone().unwrap())
-                ),
-                None => format!("printf(\"%d\\n\", {});", true),
+                Some(expr) => Self::compile_expr_println(expr, String::from("\\n")),
+                None => String::from("printf(\"true\\n\");"),
             })
             .unwrap_or_else(String::new)
     }
@@ -123,11 +439,8 @@ impl Std {
             return match &arg.value {
                 Some(ExprToken::Literal(l)) => format!("printf(\"{}\");", l),
                 Some(ExprToken::Variable(l)) => Self::compile_var_println(l, String::new()),
-                Some(_) => format!(
-                    "printf(\"%d\", {});",
-                    CLang::process_expr_token(arg.value.clone().unwrap())
-                ),
-                None => format!("printf(\"%d\", {});", true),
+                Some(expr) => Self::compile_expr_println(expr, String::new()),
+                None => String::from("printf(\"true\");"),
             };
         }
         String::new()
@@ -142,18 +455,20 @@ impl Std {
                     "return {};",
                     CLang::process_expr_token(arg.value.clone().unwrap())
                 ),
-                None => format!("return true;"),
+                None => String::from("return;"),
             };
         }
-        String::new()
+        String::from("return;")
     }
 
     pub fn compile_inc(call: CallStruct) -> String {
         if let Some(arg) = call.args.iter().find(|a: &&CallArgStruct| a.name.eq("arg")) {
             return match &arg.value {
-                Some(ExprToken::Variable(l)) if !l.is_func => format!("{}++;", l.name),
+                Some(ExprToken::Variable(l)) if !l.is_func && l.data_type.is_integer() => {
+                    format!("{}++;", l.name)
+                }
                 Some(ExprToken::Variable(l)) => SimpleError::error(
-                    &format!("Cannot increment non-variable type `{}`", l.name),
+                    &format!("Cannot increment non-integer variable `{}`", l.name),
                     ErrorKind::TypeCheck,
                 ),
                 _ => SimpleError::error("Cannot increment non-variable type", ErrorKind::TypeCheck),
@@ -165,9 +480,11 @@ impl Std {
     pub fn compile_dec(call: CallStruct) -> String {
         if let Some(arg) = call.args.iter().find(|a: &&CallArgStruct| a.name.eq("arg")) {
             return match &arg.value {
-                Some(ExprToken::Variable(l)) if !l.is_func => format!("{}--;", l.name),
+                Some(ExprToken::Variable(l)) if !l.is_func && l.data_type.is_integer() => {
+                    format!("{}--;", l.name)
+                }
                 Some(ExprToken::Variable(l)) => SimpleError::error(
-                    &format!("Cannot decrement non-variable type `{}`", l.name),
+                    &format!("Cannot decrement non-integer variable `{}`", l.name),
                     ErrorKind::TypeCheck,
                 ),
                 _ => SimpleError::error("Cannot decrement non-variable type", ErrorKind::TypeCheck),
@@ -175,4 +492,109 @@ impl Std {
         }
         String::new()
     }
+
+    /// Compiles a call to one of the conversion functions, if `call` is one
+    pub fn compile_conversion(call: &CallStruct) -> Option<String> {
+        if !call.builtin
+            || !["to_str", "to_int", "to_bool", "parse_int"].contains(&call.calling_name.as_str())
+        {
+            return None;
+        }
+
+        let arg = |name: &str| -> ExprToken {
+            call.args
+                .iter()
+                .find(|a: &&CallArgStruct| a.name.eq(name))
+                .and_then(|a: &CallArgStruct| a.value.clone())
+                .unwrap()
+        };
+
+        let value: ExprToken = arg("arg");
+        let from: DataType = value.resolved_type();
+        let value: String = CLang::process_expr_token(value);
+
+        Some(match call.calling_name.as_str() {
+            "to_str" => match from {
+                DataType::Bool => format!("(({}) ? \"true\" : \"false\");", value),
+                DataType::U8 | DataType::U16 | DataType::U32 | DataType::U64 => {
+                    format!("h8_uint_to_str((uint64_t)({}));", value)
+                }
+                _ => format!("h8_int_to_str((int64_t)({}));", value),
+            },
+            "to_int" => format!("(({}) ? 1 : 0);", value),
+            "to_bool" => format!("(({}) != 0);", value),
+            "parse_int" => format!(
+                "h8_parse_int({}, &{});",
+                value,
+                CLang::process_expr_token(arg("ok"))
+            ),
+            _ => unreachable!(),
+        })
+    }
+
+    pub fn compile_list_new(element_type: DataType) -> String {
+        format!(
+            "h8_list_new(sizeof({}))",
+            CLang::convert_types(element_type)
+        )
+    }
+
+    /// Compiles a call to one of the list functions, if `call` is one
+    pub fn compile_list_call(call: &CallStruct) -> Option<String> {
+        if !call.builtin
+            || !["push", "pop", "len", "get", "set"].contains(&call.calling_name.as_str())
+        {
+            return None;
+        }
+
+        let arg = |name: &str| -> ExprToken {
+            call.args
+                .iter()
+                .find(|a: &&CallArgStruct| a.name.eq(name))
+                .and_then(|a: &CallArgStruct| a.value.clone())
+                .unwrap()
+        };
+
+        let list: ExprToken = arg("list");
+        let item_type: String = match list.resolved_type() {
+            DataType::List(element_type) => CLang::convert_types(*element_type),
+            _ => unreachable!(),
+        };
+        let list: String = CLang::process_expr_token(list);
+
+        Some(match call.calling_name.as_str() {
+            "push" => format!(
+                "*({}*)h8_list_push({}) = {};",
+                item_type,
+                list,
+                CLang::process_expr_token(arg("value"))
+            ),
+            "pop" => format!("*({}*)h8_list_pop({}, {});", item_type, list, call.line),
+            "len" => format!("{}->len;", list),
+            "get" => format!(
+                "*({}*)h8_list_at({}, {}, {});",
+                item_type,
+                list,
+                CLang::process_expr_token(arg("at")),
+                call.line
+            ),
+            "set" => format!(
+                "*({}*)h8_list_at({}, {}, {}) = {};",
+                item_type,
+                list,
+                CLang::process_expr_token(arg("at")),
+                call.line,
+                CLang::process_expr_token(arg("value"))
+            ),
+            _ => unreachable!(),
+        })
+    }
+
+    pub fn compile_break() -> String {
+        String::from("break;")
+    }
+
+    pub fn compile_continue() -> String {
+        String::from("continue;")
+    }
 }
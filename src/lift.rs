@@ -0,0 +1,620 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use crate::{
+    code_tree::types::{
+        AssignEnum, BlockStruct, BlockType, CallStruct, CaptureStruct, DataType, DefinitionType,
+        FunctionDefinitionStruct, NodeType, ServiceBlockType,
+    },
+    errors::{simple::SimpleError, ErrorKind},
+    math::{ExprToken, VariableType},
+};
+
+/// Names visible at some point of the program
+#[derive(Clone, Default)]
+struct Scope {
+    locals: HashMap<String, (DataType, bool)>, // Variables of enclosing blocks, with const flag
+    functions: HashMap<String, Vec<String>>,   // Function names and the C names of their overloads
+    global: bool,                              // Inside `<head>`, where variables are not locals
+}
+
+/// What a nested function uses from enclosing blocks
+#[derive(Default)]
+struct FunctionUses {
+    candidates: HashMap<String, (DataType, bool)>,
+    variables: HashSet<String>,
+    calls: HashSet<String>,
+}
+
+#[derive(Default)]
+struct Lifter {
+    #[allow(clippy::vec_box)]
+    lifted: Vec<Box<NodeType>>,
+    type_names: HashSet<String>,
+    function_names: HashSet<String>,
+    uses: HashMap<String, FunctionUses>,
+    calls: Vec<(String, String, HashSet<String>)>, // C name, name and variables visible at each call
+    top_level: Vec<(String, String)>, // Name and C name of functions declared outside functions
+}
+
+/// Moves functions and types to `<head>`, so they are emitted at file scope.
+/// Variables a function uses from enclosing blocks become pointer arguments.
+/// Returns the functions used as values
+pub fn lift(tree: &mut NodeType) -> HashSet<String> {
+    let mut lifter: Lifter = Lifter::default();
+    lifter.lift_program(tree);
+    let NodeType::BLOCK(html) = tree else {
+        unreachable!()
+    };
+
+    let captures: HashMap<String, BTreeSet<String>> = lifter.resolve_captures();
+    lifter.check_calls(&captures);
+    lifter
+        .lifted
+        .iter_mut()
+        .for_each(|node: &mut Box<NodeType>| {
+            if let NodeType::DEFINITION(DefinitionType::Function(fds)) = node.as_mut() {
+                let names: &BTreeSet<String> = &captures[&fds.name];
+                let candidates = &lifter.uses[&fds.name].candidates;
+                fds.captures = names
+                    .iter()
+                    .map(|name: &String| CaptureStruct {
+                        name: name.clone(),
+                        data_type: candidates[name].0.clone(),
+                        is_const: candidates[name].1,
+                    })
+                    .collect();
+                fds.children
+                    .iter_mut()
+                    .for_each(|child| dereference(child, names));
+            }
+        });
+
+    if let NodeType::BLOCK(head) = html.children[0].as_mut() {
+        head.children.append(&mut lifter.lifted);
+    }
+
+    let mut values: HashSet<String> = HashSet::new();
+    html.children
+        .iter_mut()
+        .for_each(|block: &mut Box<NodeType>| {
+            make_closures(block, &captures, &BTreeSet::new(), &mut values)
+        });
+    values
+}
+
+/// Non-const variables of `<main>` that functions declared outside functions use,
+/// directly or through the functions they call, by function name
+pub fn main_state(mut tree: NodeType) -> Vec<(String, String)> {
+    let mut lifter: Lifter = Lifter::default();
+    lifter.lift_program(&mut tree);
+
+    let captures: HashMap<String, BTreeSet<String>> = lifter.resolve_captures();
+    lifter
+        .top_level
+        .iter()
+        .flat_map(|(name, c_name)| {
+            let candidates = &lifter.uses[c_name].candidates;
+            captures[c_name]
+                .iter()
+                .filter(|v| !candidates[*v].1)
+                .map(|v: &String| (name.clone(), v.clone()))
+        })
+        .collect()
+}
+
+impl Lifter {
+    fn lift_program(&mut self, tree: &mut NodeType) {
+        let NodeType::BLOCK(html) = tree else {
+            unreachable!()
+        };
+        let mut scope: Scope = Scope {
+            global: true,
+            ..Scope::default()
+        };
+        html.children
+            .iter_mut()
+            .for_each(|block: &mut Box<NodeType>| {
+                let NodeType::BLOCK(block_struct) = block.as_mut() else {
+                    unreachable!()
+                };
+                scope.global = block_struct.tag == BlockType::Head;
+                self.lift_children(&mut block_struct.children, &mut scope, None);
+            });
+    }
+
+    #[allow(clippy::vec_box)]
+    fn lift_children(
+        &mut self,
+        children: &mut Vec<Box<NodeType>>,
+        scope: &mut Scope,
+        current: Option<&str>,
+    ) {
+        // Functions can be called before their definition
+        let mut overloads: HashMap<String, Vec<String>> = HashMap::new();
+        children.iter().for_each(|child| {
+            if let NodeType::DEFINITION(DefinitionType::Function(fds)) = child.as_ref() {
+                let c_name: String = match fds.must_be_compiled {
+                    true => self.c_name(&fds.name),
+                    false => fds.name.clone(),
+                };
+                overloads.entry(fds.name.clone()).or_default().push(c_name);
+            }
+        });
+        scope.functions.extend(overloads);
+
+        let mut declared: HashMap<String, usize> = HashMap::new();
+        let mut kept: Vec<Box<NodeType>> = Vec::new();
+        children.drain(..).for_each(|mut child: Box<NodeType>| {
+            match child.as_mut() {
+                NodeType::DEFINITION(DefinitionType::Function(fds)) if fds.must_be_compiled => {
+                    let index: &mut usize = declared.entry(fds.name.clone()).or_default();
+                    let c_name: String = scope.functions[&fds.name][*index].clone();
+                    *index += 1;
+                    if current.is_none() {
+                        self.top_level.push((fds.name.clone(), c_name.clone()));
+                    }
+                    fds.name = c_name;
+
+                    let mut body_scope: Scope = scope.clone();
+                    body_scope.global = false;
+                    let mut candidates = scope.locals.clone();
+                    fds.args.iter().for_each(|arg| {
+                        candidates.remove(&arg.name);
+                        body_scope.functions.remove(&arg.name);
+                        body_scope
+                            .locals
+                            .insert(arg.name.clone(), (arg.data_type.clone(), false));
+                    });
+                    self.uses.insert(
+                        fds.name.clone(),
+                        FunctionUses {
+                            candidates,
+                            ..FunctionUses::default()
+                        },
+                    );
+
+                    let name: String = fds.name.clone();
+                    self.lift_children(&mut fds.children, &mut body_scope, Some(&name));
+                    return self.lifted.push(child);
+                }
+                // Std functions keep their place among the overloads
+                NodeType::DEFINITION(DefinitionType::Function(fds)) => {
+                    *declared.entry(fds.name.clone()).or_default() += 1;
+                }
+                NodeType::DEFINITION(DefinitionType::Struct(sds)) => {
+                    let name: String = sds.name.clone();
+                    self.add_type(name);
+                    return self.lifted.push(child);
+                }
+                NodeType::DEFINITION(DefinitionType::Enum(eds)) => {
+                    let name: String = eds.name.clone();
+                    self.add_type(name);
+                    return self.lifted.push(child);
+                }
+                _ => {}
+            }
+
+            self.lift_node(&mut child, scope, current);
+            kept.push(child);
+        });
+        *children = kept;
+    }
+
+    fn lift_node(&mut self, node: &mut NodeType, scope: &mut Scope, current: Option<&str>) {
+        if let Some(call) = call_mut(node) {
+            match scope.functions.get(&call.calling_name) {
+                Some(c_names) => {
+                    let c_name: String = c_names[call.overload].clone();
+                    self.note_site(&c_name, &call.calling_name, scope);
+                    call.calling_name = c_name;
+                    self.note_call(current, &call.calling_name);
+                }
+                // Function values are called through a variable
+                None => self.note_variable(current, &call.calling_name),
+            }
+        }
+        if let NodeType::ASSIGN(assign_struct) = node {
+            self.note_variable(current, &assign_struct.name);
+        }
+        exprs_mut(node)
+            .into_iter()
+            .for_each(|expr: &mut ExprToken| {
+                expr.for_each_variable(&mut |var: &mut VariableType| match var.is_func {
+                    true => {
+                        // Overloaded functions cannot be values
+                        if let Some(c_names) = scope.functions.get(&var.name) {
+                            let c_name: String = c_names[0].clone();
+                            self.note_site(&c_name, &var.name, scope);
+                            var.name = c_name;
+                        }
+                        // A function value copies the captures of the function
+                        self.note_call(current, &var.name);
+                    }
+                    false => self.note_variable(current, &var.name),
+                })
+            });
+
+        // Variables visible to the nested blocks
+        let mut inner: Scope = scope.clone();
+        match node {
+            NodeType::DEFINITION(DefinitionType::Variable(vds)) if !scope.global => {
+                scope
+                    .locals
+                    .insert(vds.name.clone(), (vds.data_type.clone(), vds.is_const));
+            }
+            NodeType::DEFINITION(DefinitionType::Array(ads)) if !scope.global => {
+                let len: usize = match ads.len {
+                    ExprToken::Number(n) => n as usize,
+                    _ => unreachable!(),
+                };
+                scope.locals.insert(
+                    ads.name.clone(),
+                    (
+                        DataType::Array(Box::new(ads.element_type.clone()), len),
+                        false,
+                    ),
+                );
+            }
+            NodeType::ServiceBlock(ServiceBlockType::For(for_struct)) => {
+                inner
+                    .locals
+                    .insert(for_struct.iter_name.clone(), (DataType::Int, false));
+            }
+            NodeType::ServiceBlock(ServiceBlockType::ForEach(for_each_struct)) => {
+                let element_type: DataType = match for_each_struct.iterable.resolved_type() {
+                    DataType::Array(element_type, _) => *element_type,
+                    _ => unreachable!(),
+                };
+                inner
+                    .locals
+                    .insert(for_each_struct.item_name.clone(), (element_type, false));
+            }
+            _ => {}
+        }
+
+        children_mut(node).into_iter().for_each(|children| {
+            self.lift_children(children, &mut inner.clone(), current);
+        });
+    }
+
+    /// C names of functions must be unique, while nested functions in different blocks may share a name.
+    /// They are prefixed so that they cannot clash with C keywords or the C library
+    fn c_name(&mut self, name: &str) -> String {
+        let mut c_name: String = format!("h8_fn_{}", name);
+        let mut n: usize = 1;
+        while self.function_names.contains(&c_name) {
+            n += 1;
+            c_name = format!("h8_fn_{}_{}", name, n);
+        }
+        self.function_names.insert(c_name.clone());
+        c_name
+    }
+
+    fn add_type(&mut self, name: String) {
+        if !self.type_names.insert(name.clone()) {
+            SimpleError::error(
+                &format!("Type `{}` is declared more than once", name),
+                ErrorKind::DefinitionCheck,
+            );
+        }
+    }
+
+    fn note_variable(&mut self, current: Option<&str>, name: &str) {
+        if let Some(uses) = current.and_then(|c| self.uses.get_mut(c)) {
+            uses.variables.insert(name.to_string());
+        }
+    }
+
+    fn note_site(&mut self, c_name: &str, name: &str, scope: &Scope) {
+        self.calls.push((
+            c_name.to_string(),
+            name.to_string(),
+            scope.locals.keys().cloned().collect(),
+        ));
+    }
+
+    fn note_call(&mut self, current: Option<&str>, name: &str) {
+        if let Some(uses) = current.and_then(|c| self.uses.get_mut(c)) {
+            uses.calls.insert(name.to_string());
+        }
+    }
+
+    /// A function captures the enclosing variables it uses, and the ones
+    /// needed by the functions it calls
+    fn resolve_captures(&self) -> HashMap<String, BTreeSet<String>> {
+        let mut captures: HashMap<String, BTreeSet<String>> = self
+            .uses
+            .iter()
+            .map(|(name, uses)| {
+                let names = uses
+                    .variables
+                    .iter()
+                    .filter(|v| uses.candidates.contains_key(*v))
+                    .cloned()
+                    .collect();
+                (name.clone(), names)
+            })
+            .collect();
+
+        let mut changed: bool = true;
+        while changed {
+            changed = false;
+            self.uses.iter().for_each(|(name, uses)| {
+                let needed: Vec<String> = uses
+                    .calls
+                    .iter()
+                    .filter_map(|call| captures.get(call))
+                    .flatten()
+                    .filter(|v| uses.candidates.contains_key(*v))
+                    .cloned()
+                    .collect();
+                needed.into_iter().for_each(|v: String| {
+                    changed |= captures.get_mut(name).unwrap().insert(v);
+                });
+            });
+        }
+
+        captures
+    }
+
+    /// Functions can be called before their definition, but not before
+    /// the variables they capture, directly or through the functions they call
+    fn check_calls(&self, captures: &HashMap<String, BTreeSet<String>>) {
+        self.calls.iter().for_each(|(c_name, name, visible)| {
+            let Some(names) = captures.get(c_name) else {
+                return;
+            };
+            if let Some(missing) = names.iter().find(|v| !visible.contains(*v)) {
+                SimpleError::error(
+                    &format!(
+                        "Variable `{}` used by function `{}` is not defined where `{}` is called",
+                        missing, name, name
+                    ),
+                    ErrorKind::DefinitionCheck,
+                );
+            }
+        });
+    }
+}
+
+/// Replaces uses of captured variables with dereferenced pointers
+fn dereference(node: &mut NodeType, names: &BTreeSet<String>) {
+    let pointer = |name: &str| format!("(*{})", name);
+
+    if let NodeType::ASSIGN(assign_struct) = node {
+        if names.contains(&assign_struct.name) {
+            assign_struct.name = pointer(&assign_struct.name);
+        }
+    }
+    if let Some(call) = call_mut(node) {
+        if names.contains(&call.calling_name) {
+            call.calling_name = pointer(&call.calling_name);
+        }
+    }
+    exprs_mut(node)
+        .into_iter()
+        .for_each(|expr: &mut ExprToken| {
+            expr.for_each_variable(&mut |var: &mut VariableType| {
+                if !var.is_func && names.contains(&var.name) {
+                    var.name = pointer(&var.name);
+                }
+            })
+        });
+    children_mut(node).into_iter().for_each(|children| {
+        children
+            .iter_mut()
+            .for_each(|child| dereference(child, names))
+    });
+}
+
+/// Replaces functions used as values with `h8_make_*` calls, which copy the captured
+/// variables. `pointers` are the captures of the enclosing function
+fn make_closures(
+    node: &mut NodeType,
+    captures: &HashMap<String, BTreeSet<String>>,
+    pointers: &BTreeSet<String>,
+    values: &mut HashSet<String>,
+) {
+    let pointers: &BTreeSet<String> = match node {
+        NodeType::DEFINITION(DefinitionType::Function(fds)) => {
+            captures.get(&fds.name).unwrap_or(pointers)
+        }
+        _ => pointers,
+    };
+
+    exprs_mut(node)
+        .into_iter()
+        .for_each(|expr: &mut ExprToken| {
+            expr.for_each_variable(&mut |var: &mut VariableType| {
+                let Some(names) = captures.get(&var.name).filter(|_| var.is_func) else {
+                    return;
+                };
+                let args: Vec<String> = names
+                    .iter()
+                    .map(|name: &String| match pointers.contains(name) {
+                        true => name.clone(),
+                        false => format!("&{}", name),
+                    })
+                    .collect();
+                values.insert(var.name.clone());
+                var.name = format!("h8_make_{}({})", var.name, args.join(", "));
+            })
+        });
+    children_mut(node).into_iter().for_each(|children| {
+        children
+            .iter_mut()
+            .for_each(|child| make_closures(child, captures, pointers, values))
+    });
+}
+
+/// Copy of a generic function with its type parameters replaced by `type_args`
+pub fn instantiate(
+    fds: &FunctionDefinitionStruct,
+    type_args: &[DataType],
+    name: String,
+) -> FunctionDefinitionStruct {
+    let bindings: HashMap<String, DataType> = fds
+        .generics
+        .iter()
+        .cloned()
+        .zip(type_args.iter().cloned())
+        .collect();
+
+    let mut instance: FunctionDefinitionStruct = fds.clone();
+    instance.name = name;
+    instance.generics = Vec::new();
+    instance.data_type = instance.data_type.substitute(&bindings);
+    instance
+        .args
+        .iter_mut()
+        .for_each(|arg| arg.data_type = arg.data_type.substitute(&bindings));
+    instance
+        .children
+        .iter_mut()
+        .for_each(|child| substitute_types(child, &bindings));
+    instance
+}
+
+fn substitute_types(node: &mut NodeType, bindings: &HashMap<String, DataType>) {
+    match node {
+        NodeType::DEFINITION(DefinitionType::Variable(vds)) => {
+            vds.data_type = vds.data_type.substitute(bindings)
+        }
+        NodeType::DEFINITION(DefinitionType::Array(ads)) => {
+            ads.element_type = ads.element_type.substitute(bindings)
+        }
+        _ => {}
+    }
+    if let Some(call) = call_mut(node) {
+        call.callee = call
+            .callee
+            .as_ref()
+            .map(|callee| callee.substitute(bindings));
+        call.type_args = call
+            .type_args
+            .iter()
+            .map(|data_type: &DataType| data_type.substitute(bindings))
+            .collect();
+    }
+    exprs_mut(node)
+        .into_iter()
+        .for_each(|expr: &mut ExprToken| {
+            expr.for_each_variable(&mut |var: &mut VariableType| {
+                var.data_type = var.data_type.substitute(bindings)
+            })
+        });
+    children_mut(node).into_iter().for_each(|children| {
+        children
+            .iter_mut()
+            .for_each(|child| substitute_types(child, bindings))
+    });
+}
+
+fn call_mut(node: &mut NodeType) -> Option<&mut CallStruct> {
+    let call: &mut Box<NodeType> = match node {
+        NodeType::CALL(call_struct) => return Some(call_struct),
+        NodeType::DEFINITION(DefinitionType::Variable(vds)) => match &mut vds.value {
+            AssignEnum::Call(call) => call,
+            _ => return None,
+        },
+        NodeType::ASSIGN(assign_struct) => match &mut assign_struct.body {
+            AssignEnum::Call(call) => call,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    match call.as_mut() {
+        NodeType::CALL(call_struct) => Some(call_struct),
+        _ => None,
+    }
+}
+
+/// Expressions of a node, without the ones of its children
+fn exprs_mut(node: &mut NodeType) -> Vec<&mut ExprToken> {
+    let mut exprs: Vec<&mut ExprToken> = Vec::new();
+    match node {
+        NodeType::CALL(call_struct) => exprs.extend(call_args_mut(call_struct)),
+        NodeType::DEFINITION(DefinitionType::Variable(vds)) => {
+            exprs.extend(value_mut(&mut vds.value))
+        }
+        NodeType::DEFINITION(DefinitionType::Array(ads)) => exprs.push(&mut ads.len),
+        NodeType::ASSIGN(assign_struct) => {
+            exprs.extend(assign_struct.element.as_mut());
+            exprs.extend(value_mut(&mut assign_struct.body));
+        }
+        NodeType::ServiceBlock(ServiceBlockType::For(for_struct)) => {
+            exprs.extend([
+                &mut for_struct.start,
+                &mut for_struct.end,
+                &mut for_struct.step,
+            ]);
+        }
+        NodeType::ServiceBlock(ServiceBlockType::ForEach(for_each_struct)) => {
+            exprs.push(&mut for_each_struct.iterable)
+        }
+        NodeType::ServiceBlock(ServiceBlockType::If(if_struct)) => exprs.extend(
+            if_struct
+                .branches
+                .iter_mut()
+                .map(|branch| &mut branch.condition),
+        ),
+        NodeType::ServiceBlock(ServiceBlockType::While(while_struct)) => {
+            exprs.push(&mut while_struct.condition)
+        }
+        NodeType::ServiceBlock(ServiceBlockType::Match(match_struct)) => {
+            exprs.push(&mut match_struct.value)
+        }
+        _ => {}
+    }
+    exprs
+}
+
+fn value_mut(value: &mut AssignEnum) -> Vec<&mut ExprToken> {
+    match value {
+        AssignEnum::Expr(expr) => vec![expr],
+        AssignEnum::Call(call) => match call.as_mut() {
+            NodeType::CALL(call_struct) => call_args_mut(call_struct),
+            _ => Vec::new(),
+        },
+        AssignEnum::None => Vec::new(),
+    }
+}
+
+fn call_args_mut(call_struct: &mut CallStruct) -> Vec<&mut ExprToken> {
+    call_struct
+        .args
+        .iter_mut()
+        .filter_map(|arg| arg.value.as_mut())
+        .collect()
+}
+
+/// Statement lists nested in a node
+#[allow(clippy::vec_box)]
+fn children_mut(node: &mut NodeType) -> Vec<&mut Vec<Box<NodeType>>> {
+    match node {
+        NodeType::BLOCK(BlockStruct { children, .. }) => vec![children],
+        NodeType::DEFINITION(DefinitionType::Function(fds)) => vec![&mut fds.children],
+        NodeType::ServiceBlock(ServiceBlockType::For(for_struct)) => {
+            vec![&mut for_struct.children]
+        }
+        NodeType::ServiceBlock(ServiceBlockType::ForEach(for_each_struct)) => {
+            vec![&mut for_each_struct.children]
+        }
+        NodeType::ServiceBlock(ServiceBlockType::While(while_struct)) => {
+            vec![&mut while_struct.children]
+        }
+        NodeType::ServiceBlock(ServiceBlockType::If(if_struct)) => if_struct
+            .branches
+            .iter_mut()
+            .map(|branch| &mut branch.children)
+            .chain(if_struct.else_children.as_mut())
+            .collect(),
+        NodeType::ServiceBlock(ServiceBlockType::Match(match_struct)) => match_struct
+            .cases
+            .iter_mut()
+            .map(|case| &mut case.children)
+            .chain(match_struct.default.as_mut())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
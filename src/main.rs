@@ -15,14 +15,20 @@ mod definitions;
 mod errors;
 mod iter;
 mod libs;
+mod lift;
 mod math;
 mod parser;
 mod types;
 
 fn main() -> Result<()> {
-    let contents: String = fs::read_to_string("./example.html8")?;
+    // The first argument that is not a flag is the program to compile
+    let source: String = std::env::args()
+        .skip(1)
+        .find(|arg: &String| !arg.starts_with("--"))
+        .unwrap_or(String::from("./example.html8"));
+    let contents: String = fs::read_to_string(&source)?;
     let tree: ASTNode = Parser::new(contents).parse();
-    let code_tree: NodeType = start_generating_code_tree(tree);
+    let code_tree: NodeType = start_generating_code_tree(tree, Path::new(&source));
     let mut comp = CLang::new(code_tree);
     let code: String = comp.compile();
 
@@ -37,14 +43,19 @@ fn main() -> Result<()> {
     let mut file: fs::File = fs::File::create(&file_path)?;
     writeln!(file, "{}", code)?;
 
-    let args: Vec<&str> = vec![
+    let mut args: Vec<&str> = vec![
         &file_path,
         "-o",
         &out_path,
         "-w",
-        "-std=gnu99",
+        "-std=c99",
         "-Wimplicit-int",
     ];
+
+    // Release builds drop runtime checks such as array bounds checking
+    if std::env::args().any(|arg: String| arg == "--release") {
+        args.extend(["-O2", "-DNDEBUG"]);
+    }
     let compiler: &str = "gcc";
     let compile_out: Output = Command::new(compiler).args(&args).output()?;
 
@@ -60,10 +71,11 @@ fn main() -> Result<()> {
         exit(-1)
     });
 
-    println!(
-        "{}",
-        String::from_utf8_lossy(&Command::new("./output/code").output()?.stdout)
-    );
+    let run_out: Output = Command::new("./output/code").output()?;
+    println!("{}", String::from_utf8_lossy(&run_out.stdout));
+    eprint!("{}", String::from_utf8_lossy(&run_out.stderr));
+
+    (!run_out.status.success()).then(|| exit(run_out.status.code().unwrap_or(-1)));
 
     Ok(())
 }
@@ -1,5 +1,7 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
 
+use crate::code_tree::get_data_type;
 use crate::code_tree::types::AssignEnum;
 use crate::errors::simple::SimpleError;
 use crate::errors::ErrorKind;
@@ -27,24 +29,39 @@ impl VariableType {
 
 #[derive(Debug, Clone)]
 pub enum ExprToken {
-    Number(i32),
+    Number(i128),
     Variable(VariableType),
     Literal(String),
+    Bool(bool),
     Add(Box<ExprToken>, Box<ExprToken>),
     Sub(Box<ExprToken>, Box<ExprToken>),
     Mul(Box<ExprToken>, Box<ExprToken>),
     Div(Box<ExprToken>, Box<ExprToken>),
     Pow(Box<ExprToken>, Box<ExprToken>),
+    Cast(Box<ExprToken>, DataType),
+    Eq(Box<ExprToken>, Box<ExprToken>),
+    Ne(Box<ExprToken>, Box<ExprToken>),
+    Lt(Box<ExprToken>, Box<ExprToken>),
+    Gt(Box<ExprToken>, Box<ExprToken>),
+    Le(Box<ExprToken>, Box<ExprToken>),
+    Ge(Box<ExprToken>, Box<ExprToken>),
+    If(Box<ExprToken>, Box<ExprToken>, Box<ExprToken>),
+    Index(Box<ExprToken>, Box<ExprToken>, usize), // Array, index and source line
+    Field(Box<ExprToken>, VariableType),          // Struct and its field
+    Struct(String, Vec<(String, ExprToken)>),     // Struct name and field values
+    Variant(String, String),                      // Enum name and value
 }
 
 pub struct MathParser {
     iter: Iter<char>,
+    line: usize,
 }
 
 impl MathParser {
-    pub fn new<I: IntoIterator<Item = char>>(iter: I) -> Self {
+    pub fn new<I: IntoIterator<Item = char>>(iter: I, line: usize) -> Self {
         Self {
             iter: Iter::from(iter),
+            line,
         }
     }
 
@@ -53,11 +70,18 @@ impl MathParser {
             Some('.') => todo!("Float not yet implemented"),
             Some('(') | Some(')') => todo!("Parentheses not implemented yet"),
             Some('"') => self.process_literal(),
+            Some('-') => {
+                self.iter.next();
+                ExprToken::Sub(
+                    Box::new(ExprToken::Number(0)),
+                    Box::new(self.parse_exponent()),
+                )
+            }
             Some(ch) if ch.is_whitespace() => {
                 self.iter.next();
                 self.parse_primary()
             }
-            Some(ch) if ch.is_numeric() => self.process_number(),
+            Some(ch) if ch.is_ascii_digit() => self.process_number(),
             Some(ch) if ch.is_alphabetic() => self.process_var(),
             Some(ch) => SimpleError::error(
                 &format!("Unexpected char: {}", ch),
@@ -68,6 +92,44 @@ impl MathParser {
     }
 
     pub fn parse_expr(&mut self) -> ExprToken {
+        let mut node: ExprToken = self.parse_sum();
+
+        while let Some(char) = self.iter.peek() {
+            match char {
+                '=' | '!' | '<' | '>' => {
+                    let mut op: String = String::new();
+                    op.extend(std::iter::from_fn(|| {
+                        self.iter.peek().and_then(|ch: char| {
+                            "=!<>".contains(ch).then(|| self.iter.next().unwrap())
+                        })
+                    }));
+
+                    let (l, r) = (Box::new(node), Box::new(self.parse_sum()));
+                    node = match op.as_str() {
+                        "==" => ExprToken::Eq(l, r),
+                        "!=" => ExprToken::Ne(l, r),
+                        "<" => ExprToken::Lt(l, r),
+                        ">" => ExprToken::Gt(l, r),
+                        "<=" => ExprToken::Le(l, r),
+                        ">=" => ExprToken::Ge(l, r),
+                        _ => SimpleError::error(
+                            &format!("Unknown operator `{}`", op),
+                            ErrorKind::MathProcessing,
+                        ),
+                    };
+                }
+                ch if ch.is_whitespace() => {
+                    self.iter.next();
+                    continue;
+                }
+                _ => break,
+            }
+        }
+
+        node
+    }
+
+    fn parse_sum(&mut self) -> ExprToken {
         let mut node: ExprToken = self.parse_term();
 
         while let Some(char) = self.iter.peek() {
@@ -93,13 +155,13 @@ impl MathParser {
     }
 
     fn parse_term(&mut self) -> ExprToken {
-        let mut node: ExprToken = self.parse_exponent();
+        let mut node: ExprToken = self.parse_cast();
 
         while let Some(char) = self.iter.peek() {
             match char {
                 '*' | '/' => {
                     self.iter.next();
-                    let r: ExprToken = self.parse_exponent();
+                    let r: ExprToken = self.parse_cast();
                     node = match char {
                         '/' => ExprToken::Div(Box::new(node), Box::new(r)),
                         '*' => ExprToken::Mul(Box::new(node), Box::new(r)),
@@ -117,6 +179,33 @@ impl MathParser {
         node
     }
 
+    fn parse_cast(&mut self) -> ExprToken {
+        let mut node: ExprToken = self.parse_exponent();
+
+        while let Some(char) = self.iter.peek() {
+            match char {
+                ch if ch.is_whitespace() => {
+                    self.iter.next();
+                    continue;
+                }
+                _ if self.consume_keyword("as") => {
+                    let type_name: String = self.read_identifier();
+                    let data_type: DataType =
+                        get_data_type(type_name.clone()).unwrap_or_else(|| {
+                            SimpleError::error(
+                                &format!("Unknown type `{}` in cast", type_name),
+                                ErrorKind::MathProcessing,
+                            )
+                        });
+                    node = ExprToken::Cast(Box::new(node), data_type);
+                }
+                _ => break,
+            }
+        }
+
+        node
+    }
+
     fn parse_exponent(&mut self) -> ExprToken {
         let mut node: ExprToken = self.parse_primary();
 
@@ -138,33 +227,154 @@ impl MathParser {
         node
     }
 
+    /// Parses decimal, `0x` hexadecimal, `0b` binary and `0o` octal literals.
+    /// Digits may be separated with `_`
     fn process_number(&mut self) -> ExprToken {
         let mut buf: String = String::new();
 
         buf.extend(std::iter::from_fn(|| {
-            self.iter
-                .peek()
-                .and_then(|ch: char| ch.is_numeric().then(|| self.iter.next().unwrap()))
+            self.iter.peek().and_then(|ch: char| {
+                (ch.is_alphanumeric() || ch == '_').then(|| self.iter.next().unwrap())
+            })
         }));
 
-        ExprToken::Number(buf.parse().unwrap_or_else(|_| {
+        let (radix, digits): (u32, &str) = match buf.get(..2) {
+            Some("0x") => (16, &buf[2..]),
+            Some("0b") => (2, &buf[2..]),
+            Some("0o") => (8, &buf[2..]),
+            _ => (10, &buf[..]),
+        };
+
+        if let Some(ch) = digits
+            .chars()
+            .find(|ch: &char| *ch != '_' && !ch.is_digit(radix))
+        {
+            SimpleError::error(
+                &format!(
+                    "Invalid digit `{}` for base {} in number literal `{}`",
+                    ch, radix, buf
+                ),
+                ErrorKind::MathProcessing,
+            );
+        }
+
+        let digits: String = digits.chars().filter(|ch: &char| *ch != '_').collect();
+        if digits.is_empty() {
+            SimpleError::error(
+                &format!("Number literal `{}` has no digits", buf),
+                ErrorKind::MathProcessing,
+            );
+        }
+
+        ExprToken::Number(i128::from_str_radix(&digits, radix).unwrap_or_else(|_| {
             SimpleError::error(
-                &format!("Invalid number {}", buf),
+                &format!("Number literal `{}` is too large", buf),
                 ErrorKind::MathProcessing,
             )
         }))
     }
 
     fn process_var(&mut self) -> ExprToken {
-        let mut buf: String = String::new();
+        match self.read_identifier() {
+            name if name == "true" => ExprToken::Bool(true),
+            name if name == "false" => ExprToken::Bool(false),
+            name if name == "if" => self.process_if(),
+            name => self.process_access(ExprToken::Variable(VariableType::new(
+                name,
+                DataType::Any,
+                false,
+            ))),
+        }
+    }
 
+    /// Parses `[index]` and `.field` directly after a value
+    fn process_access(&mut self, mut node: ExprToken) -> ExprToken {
+        loop {
+            match self.iter.peek() {
+                Some('[') => {
+                    self.iter.next();
+                    let index: ExprToken = self.parse_expr();
+                    if self.iter.next() != Some(']') {
+                        SimpleError::error("Expected `]` after index", ErrorKind::MathProcessing);
+                    }
+                    node = ExprToken::Index(Box::new(node), Box::new(index), self.line);
+                }
+                Some('.') => {
+                    self.iter.next();
+                    let field: String = self.read_identifier();
+                    if field.is_empty() {
+                        SimpleError::error(
+                            "Expected field name after `.`",
+                            ErrorKind::MathProcessing,
+                        );
+                    }
+                    node = ExprToken::Field(
+                        Box::new(node),
+                        VariableType::new(field, DataType::Any, false),
+                    );
+                }
+                _ => return node,
+            }
+        }
+    }
+
+    /// Parses `if cond then a else b` after the `if` keyword
+    fn process_if(&mut self) -> ExprToken {
+        let condition: ExprToken = self.parse_expr();
+        self.expect_keyword("then");
+        let then_branch: ExprToken = self.parse_expr();
+        self.expect_keyword("else");
+        let else_branch: ExprToken = self.parse_expr();
+
+        ExprToken::If(
+            Box::new(condition),
+            Box::new(then_branch),
+            Box::new(else_branch),
+        )
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) {
+        while self.iter.peek().is_some_and(|ch: char| ch.is_whitespace()) {
+            self.iter.next();
+        }
+
+        if !self.consume_keyword(keyword) {
+            SimpleError::error(
+                &format!("Expected `{}` in conditional expression", keyword),
+                ErrorKind::MathProcessing,
+            );
+        }
+    }
+
+    fn read_identifier(&mut self) -> String {
+        while self.iter.peek().is_some_and(|ch: char| ch.is_whitespace()) {
+            self.iter.next();
+        }
+
+        let mut buf: String = String::new();
         buf.extend(std::iter::from_fn(|| {
-            self.iter
-                .peek()
-                .and_then(|ch: char| ch.is_alphanumeric().then(|| self.iter.next().unwrap()))
+            self.iter.peek().and_then(|ch: char| {
+                (ch.is_alphanumeric() || ch == '_').then(|| self.iter.next().unwrap())
+            })
         }));
+        buf
+    }
+
+    /// Consumes `keyword` if it is the next whole word in the input
+    fn consume_keyword(&mut self, keyword: &str) -> bool {
+        let rest: &[char] = &self.iter.vec[self.iter.pos.min(self.iter.vec.len())..];
+        let matches: bool = keyword
+            .chars()
+            .enumerate()
+            .all(|(i, ch)| rest.get(i) == Some(&ch))
+            && !rest
+                .get(keyword.len())
+                .is_some_and(|ch: &char| ch.is_alphanumeric() || *ch == '_');
 
-        ExprToken::Variable(VariableType::new(buf, DataType::Any, false))
+        if matches {
+            self.iter.pos += keyword.len();
+        }
+        matches
     }
 
     fn process_literal(&mut self) -> ExprToken {
@@ -174,6 +384,7 @@ impl MathParser {
         while let Some(ch) = self.iter.peek() {
             match ch {
                 '"' => {
+                    self.iter.next();
                     return ExprToken::Literal(buf);
                 }
                 ch => {
@@ -192,34 +403,333 @@ impl MathParser {
 
 impl ExprToken {
     pub fn get_type(&mut self, scope: &HashMap<String, Defined>) -> DataType {
+        self.infer_type(scope, None)
+    }
+
+    /// Same as [`ExprToken::get_type`], but lets integer literals take the `expected` type
+    pub fn get_type_as(
+        &mut self,
+        scope: &HashMap<String, Defined>,
+        expected: &DataType,
+    ) -> DataType {
+        self.infer_type(scope, Some(expected))
+    }
+
+    fn infer_type(
+        &mut self,
+        scope: &HashMap<String, Defined>,
+        hint: Option<&DataType>,
+    ) -> DataType {
+        if self.is_int_literal() {
+            let data_type: DataType = hint
+                .filter(|h: &&DataType| h.is_integer())
+                .cloned()
+                .unwrap_or(DataType::Int);
+            ExprToken::check_literal_range(self.literal_value(), &data_type);
+            return data_type;
+        }
+
+        if let Some(variant) = self.as_variant(scope) {
+            *self = variant;
+        }
+
         match self {
-            ExprToken::Number(_) => DataType::Int,
+            ExprToken::Number(_) => unreachable!(),
             ExprToken::Literal(_) => DataType::Str,
+            ExprToken::Bool(_) => DataType::Bool,
             ExprToken::Variable(var) => {
                 var.data_type = ExprToken::get_var_type(var.name.to_string(), scope);
                 var.is_func = scope
                     .get(&var.name)
-                    .map_or(false, |def| matches!(def, Defined::Function(_)));
+                    .is_some_and(|def| matches!(def, Defined::Function(_) | Defined::Overloads(_)));
                 var.data_type.clone()
             }
-            ExprToken::Add(lhs, rhs)
-            | ExprToken::Sub(lhs, rhs)
+            ExprToken::Add(lhs, rhs) => match ExprToken::infer_operands(lhs, rhs, scope, hint) {
+                DataType::Str => DataType::Str,
+                t if t.is_integer() => t,
+                t => SimpleError::error(
+                    &format!("Operation `+` is not supported for `{:?}`", t),
+                    ErrorKind::TypeCheck,
+                ),
+            },
+            ExprToken::Sub(lhs, rhs)
             | ExprToken::Mul(lhs, rhs)
             | ExprToken::Div(lhs, rhs)
-            | ExprToken::Pow(lhs, rhs) => {
-                let lhs_type = lhs.get_type(scope);
-                let rhs_type = rhs.get_type(scope);
-                if lhs_type == rhs_type {
-                    lhs_type
-                } else {
+            | ExprToken::Pow(lhs, rhs) => match ExprToken::infer_operands(lhs, rhs, scope, hint) {
+                t if t.is_integer() => t,
+                t => SimpleError::error(
+                    &format!("Arithmetic operations are not supported for `{:?}`", t),
+                    ErrorKind::TypeCheck,
+                ),
+            },
+            ExprToken::Eq(lhs, rhs) | ExprToken::Ne(lhs, rhs) => {
+                match ExprToken::infer_operands(lhs, rhs, scope, None) {
+                    t @ (DataType::Struct(_) | DataType::Fn(..) | DataType::Generic(_)) => {
+                        SimpleError::error(
+                            &format!("Cannot compare values of type `{:?}`", t),
+                            ErrorKind::TypeCheck,
+                        )
+                    }
+                    _ => DataType::Bool,
+                }
+            }
+            ExprToken::Lt(lhs, rhs)
+            | ExprToken::Gt(lhs, rhs)
+            | ExprToken::Le(lhs, rhs)
+            | ExprToken::Ge(lhs, rhs) => match ExprToken::infer_operands(lhs, rhs, scope, None) {
+                t if t.is_integer() || t == DataType::Str => DataType::Bool,
+                t => SimpleError::error(
+                    &format!("Cannot compare values of type `{:?}`", t),
+                    ErrorKind::TypeCheck,
+                ),
+            },
+            ExprToken::If(condition, then_branch, else_branch) => {
+                let condition_type: DataType = condition.infer_type(scope, None);
+                if condition_type != DataType::Bool {
                     SimpleError::error(
                         &format!(
-                            "Type mismatch for math operation: {:?} and {:?}",
-                            lhs_type, rhs_type
+                            "Condition of `if` expression must be `Bool`, got `{:?}`",
+                            condition_type
                         ),
-                        ErrorKind::MathProcessing,
+                        ErrorKind::TypeCheck,
                     );
                 }
+                ExprToken::infer_operands(then_branch, else_branch, scope, hint)
+            }
+            ExprToken::Index(array, index, line) => {
+                let element_type: DataType = match array.infer_type(scope, None) {
+                    DataType::Array(element_type, len) => {
+                        ExprToken::check_index(index, scope, len, *line);
+                        *element_type
+                    }
+                    t => SimpleError::error(
+                        &format!("Cannot index into value of type `{:?}`", t),
+                        ErrorKind::TypeCheck,
+                    ),
+                };
+                element_type
+            }
+            ExprToken::Field(value, field) => {
+                let value_type: DataType = value.infer_type(scope, None);
+                field.data_type = ExprToken::get_field_type(&value_type, &field.name, scope);
+                field.data_type.clone()
+            }
+            ExprToken::Struct(name, fields) => {
+                fields.iter_mut().for_each(|(field, value)| {
+                    let data_type: DataType = DataType::Struct(name.clone());
+                    let field_type: DataType = ExprToken::get_field_type(&data_type, field, scope);
+                    let value_type: DataType = value.infer_type(scope, Some(&field_type));
+                    if value_type != field_type {
+                        SimpleError::error(
+                            &format!(
+                                "Field `{}` of `{}` has wrong type! Expected `{:?}`, got `{:?}`",
+                                field, name, field_type, value_type
+                            ),
+                            ErrorKind::TypeCheck,
+                        );
+                    }
+                });
+                DataType::Struct(name.clone())
+            }
+            ExprToken::Variant(name, _) => DataType::Enum(name.clone()),
+            ExprToken::Cast(value, data_type) => {
+                let value_type: DataType = value.infer_type(scope, Some(data_type));
+                if !(value_type.is_integer() && data_type.is_integer()) {
+                    SimpleError::error(
+                        &format!("Cannot cast `{:?}` to `{:?}`", value_type, data_type),
+                        ErrorKind::TypeCheck,
+                    );
+                }
+                data_type.clone()
+            }
+        }
+    }
+
+    /// Turns `Color.red` into an enum value
+    fn as_variant(&self, scope: &HashMap<String, Defined>) -> Option<ExprToken> {
+        let ExprToken::Field(value, field) = self else {
+            return None;
+        };
+        let ExprToken::Variable(var) = value.as_ref() else {
+            return None;
+        };
+        let Some(Defined::Enum(eds)) = scope.get(&var.name) else {
+            return None;
+        };
+
+        if !eds.values.contains(&field.name) {
+            SimpleError::error(
+                &format!("Enum `{}` has no value `{}`", eds.name, field.name),
+                ErrorKind::TypeCheck,
+            );
+        }
+        Some(ExprToken::Variant(eds.name.clone(), field.name.clone()))
+    }
+
+    fn get_field_type(
+        data_type: &DataType,
+        field: &str,
+        scope: &HashMap<String, Defined>,
+    ) -> DataType {
+        let fields = match data_type {
+            DataType::Struct(name) => match scope.get(name) {
+                Some(Defined::Struct(sds)) => &sds.fields,
+                _ => unreachable!(),
+            },
+            t => SimpleError::error(
+                &format!("Cannot access field `{}` of type `{:?}`", field, t),
+                ErrorKind::TypeCheck,
+            ),
+        };
+
+        fields
+            .iter()
+            .find(|f| f.name == field)
+            .map(|f| f.data_type.clone())
+            .unwrap_or_else(|| {
+                SimpleError::error(
+                    &format!("Type `{:?}` has no field `{}`", data_type, field),
+                    ErrorKind::TypeCheck,
+                )
+            })
+    }
+
+    /// Checks that `index` is an integer and, when it is constant, that it fits into `len`
+    pub fn check_index(
+        index: &mut ExprToken,
+        scope: &HashMap<String, Defined>,
+        len: usize,
+        line: usize,
+    ) {
+        let index_type: DataType = index.infer_type(scope, Some(&DataType::Int));
+        if !index_type.is_integer() {
+            SimpleError::error(
+                &format!("Array index must be an integer, got `{:?}`", index_type),
+                ErrorKind::TypeCheck,
+            );
+        }
+
+        if index.is_int_literal() {
+            ExprToken::check_index_bounds(index.literal_value(), len, line);
+        }
+    }
+
+    fn check_index_bounds(index: i128, len: usize, line: usize) {
+        if index < 0 || index >= len as i128 {
+            SimpleError::error(
+                &format!(
+                    "Index `{}` is out of bounds for array of length {} at line {}",
+                    index, len, line
+                ),
+                ErrorKind::TypeCheck,
+            );
+        }
+    }
+
+    /// Infers both operand types, returning their common type
+    fn infer_operands(
+        lhs: &mut ExprToken,
+        rhs: &mut ExprToken,
+        scope: &HashMap<String, Defined>,
+        hint: Option<&DataType>,
+    ) -> DataType {
+        // A literal operand takes the type of the other side
+        let (lhs_type, rhs_type) = if lhs.is_int_literal() {
+            let rhs_type = rhs.infer_type(scope, hint);
+            (lhs.infer_type(scope, Some(&rhs_type)), rhs_type)
+        } else {
+            let lhs_type = lhs.infer_type(scope, hint);
+            (lhs_type.clone(), rhs.infer_type(scope, Some(&lhs_type)))
+        };
+
+        if lhs_type != rhs_type {
+            SimpleError::error(
+                &format!(
+                    "Type mismatch for math operation: {:?} and {:?}",
+                    lhs_type, rhs_type
+                ),
+                ErrorKind::MathProcessing,
+            );
+        }
+        lhs_type
+    }
+
+    /// Type of an already type checked expression
+    pub fn resolved_type(&self) -> DataType {
+        match self {
+            ExprToken::Number(_) => DataType::Int,
+            ExprToken::Literal(_) => DataType::Str,
+            ExprToken::Bool(_) => DataType::Bool,
+            ExprToken::Variable(var) => var.data_type.clone(),
+            ExprToken::Add(lhs, rhs)
+            | ExprToken::Sub(lhs, rhs)
+            | ExprToken::Mul(lhs, rhs)
+            | ExprToken::Div(lhs, rhs)
+            | ExprToken::Pow(lhs, rhs) => match lhs.is_int_literal() {
+                true => rhs.resolved_type(),
+                false => lhs.resolved_type(),
+            },
+            ExprToken::Cast(_, data_type) => data_type.clone(),
+            ExprToken::Eq(..)
+            | ExprToken::Ne(..)
+            | ExprToken::Lt(..)
+            | ExprToken::Gt(..)
+            | ExprToken::Le(..)
+            | ExprToken::Ge(..) => DataType::Bool,
+            ExprToken::If(_, then_branch, else_branch) => match then_branch.is_int_literal() {
+                true => else_branch.resolved_type(),
+                false => then_branch.resolved_type(),
+            },
+            ExprToken::Index(array, ..) => match array.resolved_type() {
+                DataType::Array(element_type, _) => *element_type,
+                _ => unreachable!(),
+            },
+            ExprToken::Field(_, field) => field.data_type.clone(),
+            ExprToken::Struct(name, _) => DataType::Struct(name.clone()),
+            ExprToken::Variant(name, _) => DataType::Enum(name.clone()),
+        }
+    }
+
+    /// Whether an expression of integer literals can take `data_type`,
+    /// `None` for other expressions
+    pub fn int_literal_fits(&self, data_type: &DataType) -> Option<bool> {
+        self.is_int_literal().then(|| {
+            data_type
+                .int_bounds()
+                .is_some_and(|(min, max)| (min..=max).contains(&self.literal_value()))
+        })
+    }
+
+    /// Whether the expression consists of integer literals only
+    fn is_int_literal(&self) -> bool {
+        match self {
+            ExprToken::Number(_) => true,
+            ExprToken::Add(a, b)
+            | ExprToken::Sub(a, b)
+            | ExprToken::Mul(a, b)
+            | ExprToken::Div(a, b)
+            | ExprToken::Pow(a, b) => a.is_int_literal() && b.is_int_literal(),
+            _ => false,
+        }
+    }
+
+    fn literal_value(&self) -> i128 {
+        match self.clone().optimize_rec(&HashMap::new()) {
+            ExprToken::Number(n) => n,
+            _ => unreachable!(),
+        }
+    }
+
+    fn check_literal_range(value: i128, data_type: &DataType) {
+        if let Some((min, max)) = data_type.int_bounds() {
+            if value < min || value > max {
+                SimpleError::error(
+                    &format!(
+                        "Integer literal `{}` is out of range for `{:?}` ({}..={})",
+                        value, data_type, min, max
+                    ),
+                    ErrorKind::TypeCheck,
+                );
             }
         }
     }
@@ -232,7 +742,26 @@ impl ExprToken {
             )
         }) {
             Defined::Variable(vds) => vds.data_type.clone(),
-            Defined::Function(fds) => fds.data_type.clone(),
+            Defined::Function(fds) if !fds.generics.is_empty() => SimpleError::error(
+                &format!("Generic function `{}` cannot be used as a value", var),
+                ErrorKind::TypeCheck,
+            ),
+            Defined::Function(fds) if fds.must_be_compiled => DataType::Fn(
+                fds.args.iter().map(|arg| arg.data_type.clone()).collect(),
+                Box::new(fds.data_type.clone()),
+            ),
+            Defined::Function(_) => SimpleError::error(
+                &format!("Built-in function `{}` cannot be used as a value", var),
+                ErrorKind::TypeCheck,
+            ),
+            Defined::Overloads(_) => SimpleError::error(
+                &format!("Overloaded function `{}` cannot be used as a value", var),
+                ErrorKind::TypeCheck,
+            ),
+            Defined::Struct(_) | Defined::Enum(_) | Defined::TypeParam => SimpleError::error(
+                &format!("`{}` is a type, not a value", var),
+                ErrorKind::TypeCheck,
+            ),
         }
     }
 
@@ -250,6 +779,41 @@ impl ExprToken {
         Ok(())
     }
 
+    /// Calls `f` for every variable used in the expression
+    pub fn for_each_variable(&mut self, f: &mut dyn FnMut(&mut VariableType)) {
+        match self {
+            ExprToken::Variable(var) => f(var),
+            ExprToken::Add(a, b)
+            | ExprToken::Sub(a, b)
+            | ExprToken::Mul(a, b)
+            | ExprToken::Div(a, b)
+            | ExprToken::Pow(a, b)
+            | ExprToken::Eq(a, b)
+            | ExprToken::Ne(a, b)
+            | ExprToken::Lt(a, b)
+            | ExprToken::Gt(a, b)
+            | ExprToken::Le(a, b)
+            | ExprToken::Ge(a, b)
+            | ExprToken::Index(a, b, _) => {
+                a.for_each_variable(f);
+                b.for_each_variable(f);
+            }
+            ExprToken::If(c, a, b) => {
+                c.for_each_variable(f);
+                a.for_each_variable(f);
+                b.for_each_variable(f);
+            }
+            ExprToken::Cast(a, _) | ExprToken::Field(a, _) => a.for_each_variable(f),
+            ExprToken::Struct(_, fields) => fields
+                .iter_mut()
+                .for_each(|(_, value)| value.for_each_variable(f)),
+            ExprToken::Number(_)
+            | ExprToken::Literal(_)
+            | ExprToken::Bool(_)
+            | ExprToken::Variant(..) => {}
+        }
+    }
+
     fn recursive_math_def_check(token: ExprToken, def: &mut Vec<String>) {
         match token {
             ExprToken::Variable(n) => def.push(n.name),
@@ -257,7 +821,27 @@ impl ExprToken {
             | ExprToken::Sub(a, b)
             | ExprToken::Mul(a, b)
             | ExprToken::Div(a, b)
-            | ExprToken::Pow(a, b) => {
+            | ExprToken::Pow(a, b)
+            | ExprToken::Eq(a, b)
+            | ExprToken::Ne(a, b)
+            | ExprToken::Lt(a, b)
+            | ExprToken::Gt(a, b)
+            | ExprToken::Le(a, b)
+            | ExprToken::Ge(a, b) => {
+                ExprToken::recursive_math_def_check(*a, def);
+                ExprToken::recursive_math_def_check(*b, def);
+            }
+            ExprToken::Cast(a, _) => ExprToken::recursive_math_def_check(*a, def),
+            ExprToken::Index(a, b, _) => {
+                ExprToken::recursive_math_def_check(*a, def);
+                ExprToken::recursive_math_def_check(*b, def);
+            }
+            ExprToken::Field(a, _) => ExprToken::recursive_math_def_check(*a, def),
+            ExprToken::Struct(_, fields) => fields
+                .into_iter()
+                .for_each(|(_, value)| ExprToken::recursive_math_def_check(value, def)),
+            ExprToken::If(c, a, b) => {
+                ExprToken::recursive_math_def_check(*c, def);
                 ExprToken::recursive_math_def_check(*a, def);
                 ExprToken::recursive_math_def_check(*b, def);
             }
@@ -271,9 +855,16 @@ impl ExprToken {
 
     fn optimize_rec(self, scope: &HashMap<String, Defined>) -> Self {
         match self {
-            ExprToken::Number(_) | ExprToken::Literal(_) => self,
+            ExprToken::Number(_)
+            | ExprToken::Literal(_)
+            | ExprToken::Bool(_)
+            | ExprToken::Variant(..) => self,
             ExprToken::Variable(n) => {
-                if let Some(Defined::Variable(variable)) = scope.get(&n.name) {
+                // Function values keep the captures copied at their definition
+                if let Some(Defined::Variable(variable)) = scope
+                    .get(&n.name)
+                    .filter(|_| !matches!(n.data_type, DataType::Fn(..)))
+                {
                     if let (AssignEnum::Expr(e), true) = (variable.value.clone(), variable.is_const)
                     {
                         return e.clone();
@@ -284,8 +875,16 @@ impl ExprToken {
             ExprToken::Add(a, b) => {
                 let a = a.optimize_rec(scope);
                 let b = b.optimize_rec(scope);
-                if let (ExprToken::Number(left), ExprToken::Number(right)) = (&a, &b) {
-                    return ExprToken::Number(left + right);
+                match (&a, &b) {
+                    (ExprToken::Number(left), ExprToken::Number(right)) => {
+                        return ExprToken::Number(ExprToken::fold_checked(
+                            left.checked_add(*right),
+                        ));
+                    }
+                    (ExprToken::Literal(left), ExprToken::Literal(right)) => {
+                        return ExprToken::Literal(format!("{}{}", left, right));
+                    }
+                    _ => {}
                 }
                 ExprToken::Add(Box::new(a), Box::new(b))
             }
@@ -293,7 +892,7 @@ impl ExprToken {
                 let a = a.optimize_rec(scope);
                 let b = b.optimize_rec(scope);
                 if let (ExprToken::Number(left), ExprToken::Number(right)) = (&a, &b) {
-                    return ExprToken::Number(left - right);
+                    return ExprToken::Number(ExprToken::fold_checked(left.checked_sub(*right)));
                 }
                 ExprToken::Sub(Box::new(a), Box::new(b))
             }
@@ -301,7 +900,7 @@ impl ExprToken {
                 let a = a.optimize_rec(scope);
                 let b = b.optimize_rec(scope);
                 if let (ExprToken::Number(left), ExprToken::Number(right)) = (&a, &b) {
-                    return ExprToken::Number(left * right);
+                    return ExprToken::Number(ExprToken::fold_checked(left.checked_mul(*right)));
                 }
                 ExprToken::Mul(Box::new(a), Box::new(b))
             }
@@ -321,10 +920,107 @@ impl ExprToken {
                 let a = a.optimize_rec(scope);
                 let b = b.optimize_rec(scope);
                 if let (ExprToken::Number(left), ExprToken::Number(right)) = (&a, &b) {
-                    return ExprToken::Number(left.pow(*right as u32));
+                    let exponent: u32 = (*right).try_into().unwrap_or_else(|_| {
+                        SimpleError::error(
+                            &format!("Invalid exponent `{}`", right),
+                            ErrorKind::MathProcessing,
+                        )
+                    });
+                    return ExprToken::Number(ExprToken::fold_checked(left.checked_pow(exponent)));
                 }
                 ExprToken::Pow(Box::new(a), Box::new(b))
             }
+            ExprToken::Cast(a, data_type) => {
+                ExprToken::Cast(Box::new(a.optimize_rec(scope)), data_type)
+            }
+            ExprToken::Eq(a, b) => {
+                let (a, b) = (a.optimize_rec(scope), b.optimize_rec(scope));
+                ExprToken::fold_comparison(&a, &b, Ordering::is_eq)
+                    .unwrap_or_else(|| ExprToken::Eq(Box::new(a), Box::new(b)))
+            }
+            ExprToken::Ne(a, b) => {
+                let (a, b) = (a.optimize_rec(scope), b.optimize_rec(scope));
+                ExprToken::fold_comparison(&a, &b, Ordering::is_ne)
+                    .unwrap_or_else(|| ExprToken::Ne(Box::new(a), Box::new(b)))
+            }
+            ExprToken::Lt(a, b) => {
+                let (a, b) = (a.optimize_rec(scope), b.optimize_rec(scope));
+                ExprToken::fold_comparison(&a, &b, Ordering::is_lt)
+                    .unwrap_or_else(|| ExprToken::Lt(Box::new(a), Box::new(b)))
+            }
+            ExprToken::Gt(a, b) => {
+                let (a, b) = (a.optimize_rec(scope), b.optimize_rec(scope));
+                ExprToken::fold_comparison(&a, &b, Ordering::is_gt)
+                    .unwrap_or_else(|| ExprToken::Gt(Box::new(a), Box::new(b)))
+            }
+            ExprToken::Le(a, b) => {
+                let (a, b) = (a.optimize_rec(scope), b.optimize_rec(scope));
+                ExprToken::fold_comparison(&a, &b, Ordering::is_le)
+                    .unwrap_or_else(|| ExprToken::Le(Box::new(a), Box::new(b)))
+            }
+            ExprToken::Ge(a, b) => {
+                let (a, b) = (a.optimize_rec(scope), b.optimize_rec(scope));
+                ExprToken::fold_comparison(&a, &b, Ordering::is_ge)
+                    .unwrap_or_else(|| ExprToken::Ge(Box::new(a), Box::new(b)))
+            }
+            ExprToken::If(c, a, b) => match c.optimize_rec(scope) {
+                ExprToken::Bool(true) => a.optimize_rec(scope),
+                ExprToken::Bool(false) => b.optimize_rec(scope),
+                c => ExprToken::If(
+                    Box::new(c),
+                    Box::new(a.optimize_rec(scope)),
+                    Box::new(b.optimize_rec(scope)),
+                ),
+            },
+            ExprToken::Index(a, b, line) => {
+                let (a, b) = (a.optimize_rec(scope), b.optimize_rec(scope));
+                // Indexes that became constant after folding are checked here
+                if let (DataType::Array(_, len), ExprToken::Number(index)) = (a.resolved_type(), &b)
+                {
+                    ExprToken::check_index_bounds(*index, len, line);
+                }
+                ExprToken::Index(Box::new(a), Box::new(b), line)
+            }
+            ExprToken::Field(a, field) => match a.optimize_rec(scope) {
+                // Field of a constant struct
+                ExprToken::Struct(_, fields) => fields
+                    .into_iter()
+                    .find(|(name, _)| *name == field.name)
+                    .map(|(_, value)| value)
+                    .unwrap(),
+                a => ExprToken::Field(Box::new(a), field),
+            },
+            ExprToken::Struct(name, fields) => ExprToken::Struct(
+                name,
+                fields
+                    .into_iter()
+                    .map(|(field, value)| (field, value.optimize_rec(scope)))
+                    .collect(),
+            ),
         }
     }
+
+    /// Evaluates a comparison of two constants
+    fn fold_comparison(
+        a: &ExprToken,
+        b: &ExprToken,
+        predicate: fn(Ordering) -> bool,
+    ) -> Option<ExprToken> {
+        let ordering: Ordering = match (a, b) {
+            (ExprToken::Number(left), ExprToken::Number(right)) => left.cmp(right),
+            (ExprToken::Literal(left), ExprToken::Literal(right)) => left.cmp(right),
+            (ExprToken::Bool(left), ExprToken::Bool(right)) => left.cmp(right),
+            _ => return None,
+        };
+        Some(ExprToken::Bool(predicate(ordering)))
+    }
+
+    fn fold_checked(value: Option<i128>) -> i128 {
+        value.unwrap_or_else(|| {
+            SimpleError::error(
+                "Integer overflow in constant expression",
+                ErrorKind::MathProcessing,
+            )
+        })
+    }
 }
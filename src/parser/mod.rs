@@ -46,6 +46,7 @@ impl Parser {
             name: String::new(),
             children: Vec::new(),
             props: Vec::new(),
+            line: 0,
         };
 
         let (mut buffer, mut closing_tag): (String, String) = (String::new(), String::new());
@@ -53,8 +54,18 @@ impl Parser {
             match char {
                 '<' => match iter.peek() {
                     Some('!') => Self::handle_comment(iter),
+                    // Comparison operator inside a value, e.g. `a < b` or `a <= b`
+                    Some(c)
+                        if matches!(parse_state, ParseState::Body)
+                            && (c.is_whitespace() || c == '=') =>
+                    {
+                        buffer.push(char)
+                    }
                     Some(_) => match parse_state {
-                        ParseState::None => parse_state = ParseState::Tag,
+                        ParseState::None => {
+                            tag.line = Self::current_line(iter);
+                            parse_state = ParseState::Tag
+                        }
                         ParseState::Body => {
                             buffer = buffer.trim().to_owned();
 
@@ -77,6 +88,7 @@ impl Parser {
                 },
                 '>' => match parse_state {
                     ParseState::Props | ParseState::Tag => parse_state = ParseState::Body,
+                    ParseState::Body => buffer.push(char),
                     ParseState::ClosingTag => {
                         (tag.name != closing_tag).then(|| {
                             ParserError::error(
@@ -117,6 +129,10 @@ impl Parser {
         ParserError::error("Unexpected EOF", iter);
     }
 
+    fn current_line(iter: &Iter<char>) -> usize {
+        iter.vec[..iter.pos].iter().filter(|c| **c == '\n').count() + 1
+    }
+
     fn handle_comment(iter: &mut Iter<char>) {
         iter.step_back();
 
@@ -157,6 +173,7 @@ impl Parser {
                         parse_state = PropParseState::Eq;
                         iter.next();
                     }
+                    PropParseState::Value => buffer.push(iter.next().unwrap()),
                     _ => {
                         iter.next();
                         ParserError::error("Unexpected `=`", iter)
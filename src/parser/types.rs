@@ -22,4 +22,5 @@ pub struct ASTNode {
     pub children: Vec<ASTBody>,
     pub props: Vec<ASTProp>,
     pub self_closing: bool,
+    pub line: usize,
 }
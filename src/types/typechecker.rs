@@ -1,35 +1,70 @@
 use std::collections::HashMap;
 
-use crate::code_tree::types::{ArgStruct, AssignEnum, ServiceBlockType};
+use crate::code_tree::types::{
+    ArgStruct, AssignEnum, AssignStruct, BlockType, BranchStruct, CallArgStruct, CallStruct,
+    FunctionDefinitionStruct, IfStruct, ServiceBlockType,
+};
 use crate::errors::simple::SimpleError;
 use crate::errors::ErrorKind;
 use crate::math::VariableType;
 use crate::{
     code_tree::types::{DataType, DefinitionType, NodeType, VariableDefinitionStruct},
-    definitions::Defined,
+    definitions::{bind_positional, block_functions, declare_function, match_params, Defined},
     math::ExprToken,
 };
 
+/// Function whose body is being checked, by name and return type
+type Returns<'a> = Option<(&'a str, &'a DataType)>;
+
 pub fn start_types_check(tree: &mut NodeType) {
-    check(tree, &mut HashMap::<String, Defined>::new());
+    check(tree, &mut HashMap::<String, Defined>::new(), None);
+}
+
+/// Checks a list of statements, making every function of the list callable from function bodies
+fn check_children(
+    children: &mut [Box<NodeType>],
+    defined: &mut HashMap<String, Defined>,
+    returns: Returns,
+) {
+    let functions: HashMap<String, Defined> = block_functions(children);
+
+    children.iter_mut().for_each(|child: &mut Box<NodeType>| {
+        let NodeType::DEFINITION(DefinitionType::Function(fds)) = child.as_ref() else {
+            return check(child, defined, returns);
+        };
+        let name: String = fds.name.clone();
+
+        let mut scope: HashMap<String, Defined> = defined.clone();
+        scope.extend(functions.clone());
+        check(child, &mut scope, returns);
+        defined.insert(name.clone(), scope.remove(&name).unwrap());
+    });
 }
 
-fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
+fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>, returns: Returns) {
     let mut scope: HashMap<String, Defined> = defined.clone();
 
     match tree {
         NodeType::BLOCK(ref mut block_struct) => {
-            block_struct
-                .children
-                .iter_mut()
-                .for_each(|child: &mut Box<NodeType>| {
-                    check(child, defined);
-                })
+            check_children(&mut block_struct.children, defined, returns);
+
+            // Definitions in `<head>` are visible to the whole program
+            if block_struct.tag == BlockType::Head {
+                scope.extend(defined.drain());
+            }
         }
         NodeType::DEFINITION(ref mut definition_type) => match definition_type {
             DefinitionType::Function(fds) => {
+                fds.args.iter_mut().for_each(|arg: &mut ArgStruct| {
+                    if let Some(default) = &mut arg.default {
+                        check_default(&scope, default, arg.data_type.clone(), &arg.name);
+                    }
+                });
+
+                // Arguments are only visible inside the function
+                let mut fn_scope: HashMap<String, Defined> = scope.clone();
                 fds.args.clone().into_iter().for_each(|arg: ArgStruct| {
-                    scope.insert(
+                    fn_scope.insert(
                         arg.name.clone(),
                         Defined::Variable(VariableDefinitionStruct {
                             data_type: arg.data_type.clone(),
@@ -44,76 +79,58 @@ fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
                     );
                 });
 
-                fds.children
-                    .iter_mut()
-                    .for_each(|child: &mut Box<NodeType>| {
-                        check(child, &mut scope);
-                    });
-
-                let return_node: Option<&Box<NodeType>> = fds.children.iter().find(|child| {
-                    if let NodeType::CALL(ref call) = ***child {
-                        call.calling_name == "return"
-                    } else {
-                        false
-                    }
-                });
+                let (name, data_type) = (fds.name.clone(), fds.data_type.clone());
+                check_children(&mut fds.children, &mut fn_scope, Some((&name, &data_type)));
 
-                if !matches!(fds.data_type, DataType::Void) {
-                    match return_node {
-                        Some(return_node) => match *return_node.clone() {
-                            NodeType::CALL(mut call_struct) => {
-                                let return_value = call_struct
-                                    .args
-                                    .iter_mut()
-                                    .find(|a| a.name == "arg")
-                                    .unwrap();
-
-                                let return_type = match &mut return_value.value {
-                                    Some(expr_token) => expr_token.get_type(&scope),
-                                    None => DataType::Bool,
-                                };
-
-                                if return_type != fds.data_type {
-                                    SimpleError::error(
-                                            &format!(
-                                                "Return statement inside `{}` function has wrong type: Expected {:?}, got {:?}.",
-                                                fds.name, fds.data_type, return_type
-                                            ),
-                                            ErrorKind::TypeCheck
-                                        );
-                                }
-                            }
-                            _ => SimpleError::error("Unknown return tag", ErrorKind::TypeCheck),
-                        },
-                        None => SimpleError::error(
-                            &format!("Function `{}` must have return statement", fds.name),
-                            ErrorKind::TypeCheck,
-                        ),
-                    }
+                // Internal functions have no body to check
+                if fds.must_be_compiled
+                    && !always_returns(&fds.children, &name)
+                    && data_type != DataType::Void
+                {
+                    SimpleError::error(
+                        &format!("Function `{}` must return a value on every path", name),
+                        ErrorKind::TypeCheck,
+                    );
                 }
 
-                scope.insert(fds.name.clone(), Defined::Function(fds.clone()));
+                declare_function(&mut scope, fds.clone());
             }
             DefinitionType::Variable(ref mut vds) => {
                 let value_type = match &mut vds.value {
                     AssignEnum::Expr(ref mut expr_token) => {
+                        let expr_type = expr_token.get_type_as(&scope, &vds.data_type);
                         expr_token.optimize(&scope);
-                        expr_token.get_type(&scope)
+                        expr_type
                     }
-                    AssignEnum::Call(node_type) => match *node_type.clone() {
-                        NodeType::CALL(mut call_struct) => {
-                            match scope.get(&call_struct.calling_name) {
-                                Some(Defined::Function(fds)) => {
-                                    check_call_args(&scope, &mut call_struct, fds);
-                                    fds.data_type.clone()
-                                }
-                                _ => unreachable!(),
-                            }
-                        }
+                    AssignEnum::Call(node_type) => match node_type.as_mut() {
+                        NodeType::CALL(call_struct) => check_call(&scope, call_struct)
+                            .unwrap_or_else(|| {
+                                SimpleError::error(
+                                    &format!(
+                                        "Cannot take the value of `{}` from `{}`, as it is not a function",
+                                        vds.name, call_struct.calling_name
+                                    ),
+                                    ErrorKind::TypeCheck,
+                                )
+                            }),
                         _ => unreachable!(),
                     },
-                    AssignEnum::None => unreachable!(),
+                    AssignEnum::None => vds.data_type.clone(), // Empty list
                 };
+
+                // `<let>` takes the type of its value
+                if vds.data_type == DataType::Any {
+                    if value_type == DataType::Void {
+                        SimpleError::error(
+                            &format!(
+                                "Cannot infer the type of `{}` from a call returning nothing",
+                                vds.name
+                            ),
+                            ErrorKind::TypeCheck,
+                        );
+                    }
+                    vds.data_type = value_type.clone();
+                }
                 if vds.data_type != value_type {
                     SimpleError::error(
                         &format!(
@@ -125,105 +142,724 @@ fn check(tree: &mut NodeType, defined: &mut HashMap<String, Defined>) {
                 }
                 scope.insert(vds.name.clone(), Defined::Variable(vds.clone()));
             }
+            DefinitionType::Struct(sds) => {
+                scope.insert(sds.name.clone(), Defined::Struct(sds.clone()));
+            }
+            DefinitionType::Enum(eds) => {
+                scope.insert(eds.name.clone(), Defined::Enum(eds.clone()));
+            }
+            DefinitionType::Array(ads) => {
+                let len_type: DataType = ads.len.get_type_as(&scope, &DataType::Int);
+                ads.len.optimize(&scope);
+
+                let len: usize = match ads.len {
+                    ExprToken::Number(n) if len_type.is_integer() && n > 0 => n as usize,
+                    _ => SimpleError::error(
+                        &format!(
+                            "Length of array `{}` must be a positive constant integer",
+                            ads.name
+                        ),
+                        ErrorKind::TypeCheck,
+                    ),
+                };
+
+                scope.insert(
+                    ads.name.clone(),
+                    Defined::Variable(VariableDefinitionStruct {
+                        data_type: DataType::Array(Box::new(ads.element_type.clone()), len),
+                        name: ads.name.clone(),
+                        value: AssignEnum::None,
+                        is_const: false,
+                    }),
+                );
+            }
         },
+        NodeType::CALL(ref mut call_struct) if call_struct.calling_name == "return" => {
+            check_return(&scope, call_struct, returns);
+        }
         NodeType::CALL(ref mut call_struct) => {
-            if let Some(Defined::Function(fds)) = scope.get(&call_struct.calling_name) {
-                check_call_args(&scope, call_struct, fds);
+            check_call(&scope, call_struct);
+        }
+        NodeType::ASSIGN(ref mut assign_struct) => {
+            let target_type: Option<DataType> = assign_target_type(&scope, assign_struct);
+
+            match &mut assign_struct.body {
+                AssignEnum::Expr(ref mut expr_token) => {
+                    if let Some(target_type) = target_type {
+                        let expr_type = expr_token.get_type_as(&scope, &target_type);
+                        if target_type != expr_type {
+                            SimpleError::error(
+                                &format!(
+                                    "Assign to `{}` has wrong type! Expected: `{:?}`, got `{:?}`",
+                                    assign_struct.name, target_type, expr_type
+                                ),
+                                ErrorKind::TypeCheck,
+                            );
+                        }
+                    }
+                }
+                AssignEnum::Call(node_type) => match node_type.as_mut() {
+                    NodeType::CALL(call_struct) => {
+                        let call_type: Option<DataType> = check_call(&scope, call_struct);
+
+                        if let (Some(target_type), Some(call_type)) = (target_type, call_type) {
+                            if target_type != call_type {
+                                SimpleError::error(
+                                    &format!(
+                                        "Assign to `{}` has wrong type! Expected: `{:?}`, got `{:?}`",
+                                        assign_struct.name, target_type, call_type
+                                    ),
+                                    ErrorKind::TypeCheck,
+                                );
+                            }
+                        }
+                    }
+                    _ => unreachable!(),
+                },
+                AssignEnum::None => unreachable!(),
             }
         }
-        NodeType::ASSIGN(ref mut assign_struct) => match &mut assign_struct.body {
-            AssignEnum::Expr(ref mut expr_token) => {
-                if let Some(Defined::Variable(var)) = scope.get(&assign_struct.name) {
-                    let expr_type = expr_token.get_type(&scope);
-                    if var.data_type != expr_type {
+        NodeType::ServiceBlock(sbt) => match sbt {
+            ServiceBlockType::For(for_struct) => {
+                for_struct
+                    .start
+                    .get_type_as(&scope, &DataType::Int)
+                    .ne(&DataType::Int)
+                    .then(|| {
+                        SimpleError::error(
+                            "Argument `start` inside for block has wrong type! Expected `Int`",
+                            ErrorKind::TypeCheck,
+                        )
+                    });
+                for_struct
+                    .end
+                    .get_type_as(&scope, &DataType::Int)
+                    .ne(&DataType::Int)
+                    .then(|| {
+                        SimpleError::error(
+                            "Argument `end` inside for block has wrong type! Expected `Int`",
+                            ErrorKind::TypeCheck,
+                        )
+                    });
+
+                for_struct
+                    .step
+                    .get_type_as(&scope, &DataType::Int)
+                    .ne(&DataType::Int)
+                    .then(|| {
+                        SimpleError::error(
+                            "Argument `step` inside for block has wrong type! Expected `Int`",
+                            ErrorKind::TypeCheck,
+                        )
+                    });
+
+                for_struct.start.optimize(&scope);
+                for_struct.end.optimize(&scope);
+                for_struct.step.optimize(&scope);
+
+                if let ExprToken::Number(0) = for_struct.step {
+                    SimpleError::error(
+                        "Argument `step` inside for block cannot be zero",
+                        ErrorKind::TypeCheck,
+                    );
+                }
+
+                check_children(&mut for_struct.children, defined, returns);
+            }
+            ServiceBlockType::If(if_struct) => {
+                if_struct.branches.iter_mut().for_each(|branch| {
+                    let condition_type: DataType = branch.condition.get_type(&scope);
+                    if condition_type != DataType::Bool {
                         SimpleError::error(
                             &format!(
-                                "Assign to `{}` has wrong type! Expected: `{:?}`, got `{:?}`",
-                                assign_struct.name, var.data_type, expr_type
+                                "Condition of `if` block has wrong type! Expected `Bool`, got `{:?}`",
+                                condition_type
                             ),
                             ErrorKind::TypeCheck,
                         );
                     }
+                    branch.condition.optimize(&scope);
+
+                    let mut branch_scope = scope.clone();
+                    check_children(&mut branch.children, &mut branch_scope, returns);
+                });
+
+                if let Some(children) = &mut if_struct.else_children {
+                    let mut branch_scope = scope.clone();
+                    check_children(children, &mut branch_scope, returns);
                 }
+
+                fold_branches(if_struct);
+            }
+            ServiceBlockType::ForEach(for_each_struct) => {
+                let element_type: DataType = match for_each_struct.iterable.get_type(&scope) {
+                    DataType::Array(element_type, _) => *element_type,
+                    t => SimpleError::error(
+                        &format!("Cannot iterate over value of type `{:?}`", t),
+                        ErrorKind::TypeCheck,
+                    ),
+                };
+                for_each_struct.iterable.optimize(&scope);
+
+                let mut loop_scope = scope.clone();
+                loop_scope.insert(
+                    for_each_struct.item_name.clone(),
+                    Defined::Variable(VariableDefinitionStruct {
+                        data_type: element_type,
+                        name: for_each_struct.item_name.clone(),
+                        value: AssignEnum::None,
+                        is_const: false,
+                    }),
+                );
+                check_children(&mut for_each_struct.children, &mut loop_scope, returns);
             }
-            AssignEnum::Call(node_type) => match *node_type.clone() {
-                NodeType::CALL(mut call_struct) => {
-                    let call_type = scope.get(&call_struct.calling_name);
-                    let assign_type = scope.get(&assign_struct.name);
+            ServiceBlockType::Match(match_struct) => {
+                let values: Vec<String> = match match_struct.value.get_type(&scope) {
+                    DataType::Enum(name) => match scope.get(&name) {
+                        Some(Defined::Enum(eds)) => eds.values.clone(),
+                        _ => unreachable!(),
+                    },
+                    t => SimpleError::error(
+                        &format!("Cannot match on value of type `{:?}`", t),
+                        ErrorKind::TypeCheck,
+                    ),
+                };
+                match_struct.value.optimize(&scope);
 
-                    if let (Some(Defined::Variable(var)), Some(Defined::Function(fun))) =
-                        (assign_type, call_type)
-                    {
-                        if var.data_type != fun.data_type {
+                let mut covered: Vec<&String> = Vec::new();
+                match_struct
+                    .cases
+                    .iter()
+                    .flat_map(|case| case.values.iter())
+                    .for_each(|value| {
+                        if !values.contains(value) {
                             SimpleError::error(
-                                &format!(
-                                    "Assign to `{}` has wrong type! Expected: `{:?}`, got `{:?}`",
-                                    assign_struct.name, var.data_type, fun.data_type
-                                ),
+                                &format!("Unknown case `{}` in `<match>`", value),
                                 ErrorKind::TypeCheck,
                             );
                         }
+                        if covered.contains(&value) {
+                            SimpleError::error(
+                                &format!("Case `{}` is matched more than once", value),
+                                ErrorKind::TypeCheck,
+                            );
+                        }
+                        covered.push(value);
+                    });
 
-                        check_call_args(&scope, &mut call_struct, fun);
-                    }
-                }
-                _ => unreachable!(),
-            },
-            AssignEnum::None => unreachable!(),
-        },
-        NodeType::ServiceBlock(sbt) => {
-            match sbt {
-                ServiceBlockType::For(for_struct) => {
-                    for_struct.start
-                    .get_type(&scope)
-                    .ne(&DataType::Int)
-                    .then(|| SimpleError::error(
-                        &format!("Argument `start` inside for block has wrong type! Expected `Int`"),
-                        ErrorKind::TypeCheck
-                    ));
-                    for_struct.end.get_type(&scope).ne(&DataType::Int).then(|| {
+                if match_struct.default.is_none() {
+                    if let Some(missing) = values.iter().find(|v| !covered.contains(v)) {
                         SimpleError::error(
                             &format!(
-                                "Argument `end` inside for block has wrong type! Expected `Int`"
+                                "Non-exhaustive `<match>`: missing `{}`. Add a case or `<default>`",
+                                missing
                             ),
                             ErrorKind::TypeCheck,
-                        )
+                        );
+                    }
+                }
+
+                match_struct
+                    .cases
+                    .iter_mut()
+                    .map(|case| &mut case.children)
+                    .chain(match_struct.default.iter_mut())
+                    .for_each(|children| {
+                        let mut case_scope = scope.clone();
+                        check_children(children, &mut case_scope, returns);
                     });
+            }
+            ServiceBlockType::While(while_struct) => {
+                let condition_type: DataType = while_struct.condition.get_type(&scope);
+                if condition_type != DataType::Bool {
+                    SimpleError::error(
+                        &format!(
+                            "Condition of `while` block has wrong type! Expected `Bool`, got `{:?}`",
+                            condition_type
+                        ),
+                        ErrorKind::TypeCheck,
+                    );
+                }
+                while_struct.condition.optimize(&scope);
+
+                let mut loop_scope = scope.clone();
+                check_children(&mut while_struct.children, &mut loop_scope, returns);
+            }
+        },
+    }
 
-                    for_struct.start.optimize(&scope);
-                    for_struct.end.optimize(&scope);
+    *defined = scope;
+}
 
-                    for_struct.children.iter_mut().for_each(|child| {
-                        check(child, defined);
-                    })
-                }
+/// Type of the assigned place: the variable itself or one of its elements
+fn assign_target_type(
+    scope: &HashMap<String, Defined>,
+    assign_struct: &mut AssignStruct,
+) -> Option<DataType> {
+    let var_type: DataType = match scope.get(&assign_struct.name) {
+        Some(Defined::Variable(var)) => var.data_type.clone(),
+        _ => return None,
+    };
+
+    match (&mut assign_struct.element, var_type) {
+        (Some(element), _) => {
+            let element_type: DataType = element.get_type(scope);
+            element.optimize(scope);
+            Some(element_type)
+        }
+        (None, DataType::Array(..)) => SimpleError::error(
+            &format!(
+                "Cannot assign to array `{}` as a whole, use `at` to set an element",
+                assign_struct.name
+            ),
+            ErrorKind::TypeCheck,
+        ),
+        (None, t) => Some(t),
+    }
+}
+
+/// Checks the value of `<return>` against the return type of the enclosing function
+fn check_return(scope: &HashMap<String, Defined>, call_struct: &mut CallStruct, returns: Returns) {
+    let Some((name, data_type)) = returns else {
+        SimpleError::error(
+            "`<return>` can only be used inside a function",
+            ErrorKind::TypeCheck,
+        )
+    };
+    let value: Option<&mut ExprToken> = call_struct
+        .args
+        .iter_mut()
+        .find(|a| a.name == "arg")
+        .and_then(|arg| arg.value.as_mut());
+
+    match (value, data_type) {
+        (None, DataType::Void) => {}
+        (Some(_), DataType::Void) => SimpleError::error(
+            &format!("Function `{}` is void and cannot return a value", name),
+            ErrorKind::TypeCheck,
+        ),
+        (None, _) => SimpleError::error(
+            &format!(
+                "Return statement inside `{}` function must have a value",
+                name
+            ),
+            ErrorKind::TypeCheck,
+        ),
+        (Some(expr_token), _) => {
+            let return_type: DataType = expr_token.get_type_as(scope, data_type);
+            expr_token.optimize(scope);
+
+            if return_type != *data_type {
+                SimpleError::error(
+                    &format!(
+                        "Return statement inside `{}` function has wrong type: Expected {:?}, got {:?}.",
+                        name, data_type, return_type
+                    ),
+                    ErrorKind::TypeCheck,
+                );
             }
         }
     }
+}
 
-    *defined = scope;
+/// Whether every path through the statements ends with `<return>`.
+/// Statements after one that always returns are reported, as they never run
+fn always_returns(children: &[Box<NodeType>], name: &str) -> bool {
+    let position: Option<usize> = children.iter().position(|child| returns(child, name));
+
+    if let Some(position) = position.filter(|position| position + 1 < children.len()) {
+        SimpleError::warning(
+            &format!(
+                "Code after `<return>` inside `{}` function is never run",
+                name
+            ),
+            ErrorKind::TypeCheck,
+        );
+        // Later statements are still checked for their own unreachable code
+        children[position + 1..].iter().for_each(|child| {
+            returns(child, name);
+        });
+    }
+    position.is_some()
 }
 
-fn check_call_args(
+fn returns(node: &NodeType, name: &str) -> bool {
+    match node {
+        NodeType::CALL(call_struct) => call_struct.calling_name == "return",
+        NodeType::BLOCK(block_struct) => always_returns(&block_struct.children, name),
+        NodeType::ServiceBlock(ServiceBlockType::If(if_struct)) => {
+            // Every branch is visited, so each reports its own unreachable code
+            let branches: Vec<bool> = if_struct
+                .branches
+                .iter()
+                .map(|branch| always_returns(&branch.children, name))
+                .collect();
+            let else_returns: bool = if_struct
+                .else_children
+                .as_ref()
+                .is_some_and(|children| always_returns(children, name));
+            branches.into_iter().all(|b| b) && else_returns
+        }
+        NodeType::ServiceBlock(ServiceBlockType::Match(match_struct)) => {
+            // A `<match>` without `<default>` covers every value, see the type check above
+            let cases: Vec<bool> = match_struct
+                .cases
+                .iter()
+                .map(|case| always_returns(&case.children, name))
+                .chain(
+                    match_struct
+                        .default
+                        .iter()
+                        .map(|children| always_returns(children, name)),
+                )
+                .collect();
+            cases.into_iter().all(|b| b)
+        }
+        // Loops may run zero times, but their bodies can still have unreachable code
+        NodeType::ServiceBlock(ServiceBlockType::For(for_struct)) => {
+            always_returns(&for_struct.children, name);
+            false
+        }
+        NodeType::ServiceBlock(ServiceBlockType::ForEach(for_each_struct)) => {
+            always_returns(&for_each_struct.children, name);
+            false
+        }
+        NodeType::ServiceBlock(ServiceBlockType::While(while_struct)) => {
+            always_returns(&while_struct.children, name);
+            false
+        }
+        NodeType::DEFINITION(_) | NodeType::ASSIGN(_) => false,
+    }
+}
+
+/// Removes branches whose condition is known at compile time
+fn fold_branches(if_struct: &mut IfStruct) {
+    let mut branches: Vec<BranchStruct> = Vec::new();
+
+    for branch in if_struct.branches.drain(..) {
+        match branch.condition {
+            ExprToken::Bool(false) => continue,
+            ExprToken::Bool(true) => {
+                if_struct.else_children = Some(branch.children);
+                break;
+            }
+            _ => branches.push(branch),
+        }
+    }
+
+    if_struct.branches = branches;
+}
+
+/// Defaults are copied to every call site, so they must be constants
+fn check_default(
     scope: &HashMap<String, Defined>,
-    call_struct: &mut crate::code_tree::types::CallStruct,
-    fds: &crate::code_tree::types::FunctionDefinitionStruct,
+    default: &mut ExprToken,
+    data_type: DataType,
+    name: &str,
 ) {
-    call_struct.args.iter_mut().for_each(|arg| {
-        if let Some(ags) = fds.args.iter().find(|a| a.name == arg.name) {
-            if let Some(argv) = arg.value.as_mut() {
-                let argv_type: DataType = argv.get_type(scope);
-                argv.optimize(scope);
-                if ags.data_type != DataType::Any && ags.data_type != argv_type {
-                    SimpleError::error(
-                        &format!(
-                            "Argument `{}` has wrong type! Expected: `{:?}`, got `{:?}`",
-                            ags.name, ags.data_type, argv_type
-                        ),
-                        ErrorKind::TypeCheck,
-                    );
-                }
+    let default_type: DataType = default.get_type_as(scope, &data_type);
+    if default_type != data_type {
+        SimpleError::error(
+            &format!(
+                "Default value of argument `{}` has wrong type! Expected: `{:?}`, got `{:?}`",
+                name, data_type, default_type
+            ),
+            ErrorKind::TypeCheck,
+        );
+    }
+
+    default.optimize(scope);
+    if !matches!(
+        default,
+        ExprToken::Number(_) | ExprToken::Bool(_) | ExprToken::Literal(_) | ExprToken::Variant(..)
+    ) {
+        SimpleError::error(
+            &format!("Default value of argument `{}` must be a constant", name),
+            ErrorKind::TypeCheck,
+        );
+    }
+}
+
+/// Checks a call of a function or of a function value and returns the type of the call
+fn check_call(scope: &HashMap<String, Defined>, call_struct: &mut CallStruct) -> Option<DataType> {
+    match scope.get(&call_struct.calling_name) {
+        Some(Defined::Function(fds)) => {
+            call_struct.builtin = !fds.must_be_compiled;
+            Some(check_call_args(scope, call_struct, fds))
+        }
+        Some(Defined::Overloads(overloads)) => {
+            let (index, fds) = resolve_overload(scope, call_struct, overloads);
+            call_struct.overload = index;
+            call_struct.builtin = !fds.must_be_compiled;
+            bind_positional(call_struct, fds);
+            Some(check_call_args(scope, call_struct, fds))
+        }
+        Some(Defined::Variable(vds)) if matches!(vds.data_type, DataType::Fn(..)) => {
+            Some(check_value_call(scope, call_struct, vds.data_type.clone()))
+        }
+        // `<let>` variables are only known to hold a function once their type is inferred
+        Some(Defined::Variable(vds)) => SimpleError::error(
+            &format!("Cannot call variable as function: {}", vds.name),
+            ErrorKind::TypeCheck,
+        ),
+        _ => None,
+    }
+}
+
+fn is_mutable_variable(scope: &HashMap<String, Defined>, value: &ExprToken) -> bool {
+    match value {
+        ExprToken::Variable(var) => {
+            matches!(scope.get(&var.name), Some(Defined::Variable(vds)) if !vds.is_const)
+        }
+        _ => false,
+    }
+}
+
+/// Picks the only overload whose arguments match the call
+fn resolve_overload<'a>(
+    scope: &HashMap<String, Defined>,
+    call_struct: &CallStruct,
+    overloads: &'a [FunctionDefinitionStruct],
+) -> (usize, &'a FunctionDefinitionStruct) {
+    let mut matching: Vec<(usize, &FunctionDefinitionStruct)> = overloads
+        .iter()
+        .enumerate()
+        .filter(|(_, fds)| overload_matches(scope, call_struct, fds, false))
+        .collect();
+
+    // Integer literals fit several integer types, but are `int` by default
+    let exact: Vec<(usize, &FunctionDefinitionStruct)> = matching
+        .iter()
+        .filter(|(_, fds)| overload_matches(scope, call_struct, fds, true))
+        .copied()
+        .collect();
+    if matching.len() > 1 && exact.len() == 1 {
+        matching = exact;
+    }
+
+    // Std functions are used only when no definition of the program matches
+    let own: Vec<(usize, &FunctionDefinitionStruct)> = matching
+        .iter()
+        .filter(|(_, fds)| fds.must_be_compiled)
+        .copied()
+        .collect();
+    if matching.len() > 1 && !own.is_empty() {
+        matching = own;
+    }
+
+    match matching.as_slice() {
+        [overload] => *overload,
+        [] => {
+            let types: Vec<DataType> = call_struct
+                .args
+                .iter()
+                .filter_map(|arg| arg.value.clone())
+                .map(|mut argv: ExprToken| argv.get_type(scope))
+                .collect();
+            SimpleError::error(
+                &format!(
+                    "No overload of `{}` takes arguments of types {:?}",
+                    call_struct.calling_name, types
+                ),
+                ErrorKind::TypeCheck,
+            )
+        }
+        _ => SimpleError::error(
+            &format!(
+                "Call of `{}` matches more than one overload",
+                call_struct.calling_name
+            ),
+            ErrorKind::TypeCheck,
+        ),
+    }
+}
+
+/// Whether the call arguments fit the overload by position, name and type.
+/// With `literals_as_int`, integer literals only fit `int`
+fn overload_matches(
+    scope: &HashMap<String, Defined>,
+    call_struct: &CallStruct,
+    fds: &FunctionDefinitionStruct,
+    literals_as_int: bool,
+) -> bool {
+    let Ok(params) = match_params(call_struct, fds) else {
+        return false;
+    };
+    let mut bound: Vec<(&ArgStruct, &CallArgStruct)> = Vec::new();
+    for (param, arg) in params.into_iter().zip(&call_struct.args) {
+        match param {
+            Some(param) if !bound.iter().any(|(p, _)| p.name == param.name) => {
+                bound.push((param, arg))
             }
+            _ => return false,
+        }
+    }
+
+    let mut bindings: HashMap<String, DataType> = HashMap::new();
+    fds.args
+        .iter()
+        .all(|ags| ags.default.is_some() || bound.iter().any(|(param, _)| param.name == ags.name))
+        && bound.iter().all(|(param, arg)| {
+            let Some(argv) = &arg.value else {
+                return false;
+            };
+            match argv
+                .int_literal_fits(&param.data_type)
+                .filter(|_| !literals_as_int && !param.data_type.is_generic())
+            {
+                Some(fits) => fits,
+                None => param
+                    .data_type
+                    .bind(&argv.clone().get_type(scope), &mut bindings),
+            }
+        })
+}
+
+/// Function values take their arguments in order, e.g. `<op {1} {2} />`
+fn check_value_call(
+    scope: &HashMap<String, Defined>,
+    call_struct: &mut CallStruct,
+    fn_type: DataType,
+) -> DataType {
+    let DataType::Fn(args, data_type) = &fn_type else {
+        unreachable!()
+    };
+
+    if call_struct.args.len() != args.len() {
+        SimpleError::error(
+            &format!(
+                "Function `{}` takes {} arguments, got {}",
+                call_struct.calling_name,
+                args.len(),
+                call_struct.args.len()
+            ),
+            ErrorKind::TypeCheck,
+        );
+    }
+
+    call_struct
+        .args
+        .iter_mut()
+        .zip(args)
+        .enumerate()
+        .for_each(|(i, (arg, expected_type))| {
+            let argv: &mut ExprToken = match (arg.name.as_str(), arg.value.as_mut()) {
+                ("arg", Some(argv)) => argv,
+                _ => SimpleError::error(
+                    &format!(
+                        "Arguments of function value `{}` are passed in order, e.g. `<{} {{x}} />`",
+                        call_struct.calling_name, call_struct.calling_name
+                    ),
+                    ErrorKind::TypeCheck,
+                ),
+            };
+
+            let argv_type: DataType = argv.get_type_as(scope, expected_type);
+            argv.optimize(scope);
+
+            if argv_type != *expected_type {
+                SimpleError::error(
+                    &format!(
+                        "Argument {} of `{}` has wrong type! Expected: `{:?}`, got `{:?}`",
+                        i + 1,
+                        call_struct.calling_name,
+                        expected_type,
+                        argv_type
+                    ),
+                    ErrorKind::TypeCheck,
+                );
+            }
+        });
+
+    let call_type: DataType = *data_type.clone();
+    call_struct.callee = Some(fn_type);
+    call_type
+}
+
+/// Checks call arguments and returns the type of the call.
+/// Type parameters of generic functions are bound from the arguments
+fn check_call_args(
+    scope: &HashMap<String, Defined>,
+    call_struct: &mut CallStruct,
+    fds: &FunctionDefinitionStruct,
+) -> DataType {
+    let mut bindings: HashMap<String, DataType> = HashMap::new();
+
+    // Omitted arguments take their defaults
+    fds.args.iter().for_each(|ags: &ArgStruct| {
+        if let (Some(default), false) = (
+            &ags.default,
+            call_struct.args.iter().any(|arg| arg.name == ags.name),
+        ) {
+            call_struct.args.push(CallArgStruct {
+                name: ags.name.clone(),
+                value: Some(default.clone()),
+            });
         }
     });
+
+    fds.args.iter().for_each(|ags| {
+        let Some(argv) = call_struct
+            .args
+            .iter_mut()
+            .find(|a| a.name == ags.name)
+            .and_then(|arg| arg.value.as_mut())
+        else {
+            return;
+        };
+
+        let expected_type: DataType = ags.data_type.substitute(&bindings);
+        let argv_type: DataType = match expected_type {
+            DataType::Any => argv.get_type(scope),
+            ref t if t.is_generic() => argv.get_type(scope),
+            ref t => argv.get_type_as(scope, t),
+        };
+
+        // Arguments written by the callee must be mutable variables
+        if ags.writes && !is_mutable_variable(scope, argv) {
+            SimpleError::error(
+                &format!(
+                    "Argument `{}` of `{}` must be a mutable variable",
+                    ags.name, fds.name
+                ),
+                ErrorKind::TypeCheck,
+            );
+        }
+        argv.optimize(scope);
+
+        if !ags.data_type.bind(&argv_type, &mut bindings) {
+            SimpleError::error(
+                &format!(
+                    "Argument `{}` has wrong type! Expected: `{:?}`, got `{:?}`",
+                    ags.name, expected_type, argv_type
+                ),
+                ErrorKind::TypeCheck,
+            );
+        }
+    });
+
+    // Each argument is bound to its parameter, so codegen can emit them in declaration order
+    call_struct.args.sort_by_key(|arg| {
+        fds.args
+            .iter()
+            .position(|ags: &ArgStruct| ags.name == arg.name)
+    });
+
+    if !fds.data_type.is_bound(&bindings) {
+        SimpleError::error(
+            &format!(
+                "Cannot infer the return type of `{}` call",
+                call_struct.calling_name
+            ),
+            ErrorKind::TypeCheck,
+        );
+    }
+
+    // Generic functions are compiled once per list of type arguments
+    call_struct.type_args = fds
+        .generics
+        .iter()
+        .map(|name: &String| bindings[name].clone())
+        .collect();
+    fds.data_type.substitute(&bindings)
 }
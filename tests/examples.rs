@@ -0,0 +1,38 @@
+//! Compiles and runs every program in `examples` that has an `.out` file next to it,
+//! which holds what the program is expected to print
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+#[test]
+fn examples_print_expected_output() {
+    let examples: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let mut expected: Vec<PathBuf> = fs::read_dir(&examples)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path: &PathBuf| path.extension().is_some_and(|ext| ext == "out"))
+        .collect();
+    expected.sort();
+    assert!(!expected.is_empty(), "No examples with expected output");
+
+    // The compiler writes its output next to where it runs
+    let dir: PathBuf = std::env::temp_dir().join(format!("html8-examples-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    expected.iter().for_each(|out: &PathBuf| {
+        let source: PathBuf = out.with_extension("html8");
+        let run: Output = Command::new(env!("CARGO_BIN_EXE_html8"))
+            .arg(&source)
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&run.stdout),
+            fs::read_to_string(out).unwrap(),
+            "{}",
+            source.display()
+        );
+    });
+    fs::remove_dir_all(&dir).unwrap();
+}